@@ -0,0 +1,240 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::reactor::Reactor;
+use crate::runtime::{WaitReason, Waiter, current_runtime};
+
+// 线程池最多多少个 OS 线程，任务更多时排队
+const MAX_THREADS: usize = 64;
+// 空闲这么久的线程退出
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+// 所有 runtime 共用的线程池，线程按需创建
+struct Pool {
+    state: Mutex<PoolState>,
+    work: Condvar,
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(|| Pool {
+        state: Mutex::new(PoolState {
+            jobs: VecDeque::new(),
+            threads: 0,
+            idle: 0,
+        }),
+        work: Condvar::new(),
+    })
+}
+
+impl Pool {
+    fn execute(&'static self, job: Job) {
+        let mut state = self.state.lock().unwrap();
+        state.jobs.push_back(job);
+        if state.idle > 0 || state.threads == MAX_THREADS {
+            self.work.notify_one();
+            return;
+        }
+
+        state.threads += 1;
+        drop(state);
+        thread::Builder::new()
+            .name("rustcoro-blocking".into())
+            .spawn(move || self.run())
+            .expect("failed to spawn a spawn_blocking thread");
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.idle += 1;
+            let (next, wait) = self.work.wait_timeout(state, KEEP_ALIVE).unwrap();
+            state = next;
+            state.idle -= 1;
+            if wait.timed_out() && state.jobs.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+// 工作线程和 runtime 之间的通知：完成的任务编号放进列表，然后写 eventfd 唤醒 epoll_wait
+struct Completions {
+    eventfd: OwnedFd,
+    done: Mutex<Vec<u64>>,
+}
+
+impl Completions {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Completions {
+            eventfd: unsafe { OwnedFd::from_raw_fd(fd) },
+            done: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn push(&self, token: u64) {
+        self.done.lock().unwrap().push(token);
+        let one: u64 = 1;
+        unsafe {
+            libc::write(
+                self.eventfd.as_raw_fd(),
+                (&raw const one).cast(),
+                mem::size_of::<u64>(),
+            )
+        };
+    }
+
+    // 先清空 eventfd 再取列表，之后完成的任务会重新让 eventfd 可读
+    fn take(&self) -> Vec<u64> {
+        let mut count: u64 = 0;
+        unsafe {
+            libc::read(
+                self.eventfd.as_raw_fd(),
+                (&raw mut count).cast(),
+                mem::size_of::<u64>(),
+            )
+        };
+        mem::take(&mut *self.done.lock().unwrap())
+    }
+}
+
+/// runtime 交给线程池的任务：任务完成后唤醒提交它的协程
+pub(crate) struct Blocking {
+    completions: Option<Arc<Completions>>, // 第一次提交时创建，eventfd 登记在反应器中
+    jobs: HashMap<u64, Waiter>,            // 还没有完成的任务
+    next: u64,
+}
+
+impl Blocking {
+    pub(crate) fn new() -> Self {
+        Blocking {
+            completions: None,
+            jobs: HashMap::new(),
+            next: 0,
+        }
+    }
+
+    /// 在等线程池中的任务完成的线程数量
+    pub(crate) fn waiters(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// 把 `job` 交给线程池，完成时唤醒 waiter；返回任务编号
+    pub(crate) fn submit(
+        &mut self,
+        reactor: &mut Reactor,
+        job: Job,
+        waiter: Waiter,
+    ) -> io::Result<u64> {
+        if self.completions.is_none() {
+            let completions = Completions::new()?;
+            reactor.watch(completions.eventfd.as_raw_fd())?;
+            self.completions = Some(Arc::new(completions));
+        }
+        let completions = Arc::clone(self.completions.as_ref().unwrap());
+
+        let token = self.next;
+        self.next += 1;
+        self.jobs.insert(token, waiter);
+        pool().execute(Box::new(move || {
+            job();
+            completions.push(token);
+        }));
+        Ok(token)
+    }
+
+    /// 不再等待任务，比如等待的协程被取消；任务仍然会运行完，但是不唤醒任何人
+    pub(crate) fn forget(&mut self, token: u64) {
+        self.jobs.remove(&token);
+    }
+
+    /// 任务是否还在线程池中运行
+    pub(crate) fn is_running(&self, token: u64) -> bool {
+        self.jobs.contains_key(&token)
+    }
+
+    /// 不阻塞地取出完成的任务，返回需要唤醒的等待者
+    ///
+    /// 即使没有等待中的任务也要清空 eventfd：被 forget 的任务完成后 eventfd 仍然可读，
+    /// 不清空的话水平触发的 epoll_wait 会一直立即返回。没有等待者的任务直接丢掉
+    pub(crate) fn complete(&mut self) -> Vec<Waiter> {
+        let Some(completions) = &self.completions else {
+            return Vec::new();
+        };
+        completions
+            .take()
+            .into_iter()
+            .filter_map(|token| self.jobs.remove(&token))
+            .collect()
+    }
+}
+
+// 等待的协程被取消或者超时、展开栈时撤销等待，否则 run 会一直等这个任务
+struct Pending {
+    token: u64,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        current_runtime().forget_blocking(self.token);
+    }
+}
+
+/// 在线程池中运行不会让出执行权的阻塞调用（压缩、`getaddrinfo`、同步文件 API 等），
+/// 当前协程等待它完成，其他协程继续运行
+///
+/// 线程池所有 runtime 共用，最多 64 个 OS 线程，更多的调用排队。
+/// `f` 中的 panic 在当前协程中重新抛出。当前协程被取消或者超时的话 `f` 仍然会运行完，结果被丢弃
+pub fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(None));
+    let result = Arc::clone(&slot);
+    let job = Box::new(move || {
+        let value = panic::catch_unwind(AssertUnwindSafe(f));
+        *result.lock().unwrap() = Some(value);
+    });
+
+    let rt = current_runtime();
+    let token = rt
+        .t_submit_blocking(job)
+        .expect("failed to create the spawn_blocking eventfd");
+    let _pending = Pending { token };
+    while rt.blocking_running(token) {
+        rt.t_park(WaitReason::Blocking);
+    }
+
+    match slot.lock().unwrap().take() {
+        Some(Ok(value)) => value,
+        Some(Err(payload)) => panic::resume_unwind(payload),
+        None => unreachable!("spawn_blocking job finished without a result"),
+    }
+}
@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use crate::join::JoinHandle;
+use crate::multi;
+use crate::runtime::{DEFAULT_STACK_SIZE, SpawnError, current_runtime, try_current_runtime};
+
+/// 创建协程时的配置：名字、栈大小、优先级、截止时间
+///
+/// ```ignore
+/// let handle = Builder::new()
+///     .name("worker")
+///     .stack_size(16 * 1024)
+///     .spawn(|| 1 + 1)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    pub(crate) name: Option<String>,
+    pub(crate) stack_size: usize,
+    pub(crate) priority: u8,
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder {
+            name: None,
+            stack_size: DEFAULT_STACK_SIZE,
+            priority: 0,
+            deadline: None,
+        }
+    }
+
+    /// 协程名字，出现在栈溢出等诊断信息中
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// 可用栈空间的字节数，会向上取整到页大小，默认 2 MiB
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// 优先级，数值越大越优先，默认 0
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 截止时间，`scheduler::Edf` 优先运行截止时间最近的协程
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// 按当前配置在当前 runtime 上创建协程
+    ///
+    /// 在 `MultiRuntime` 的协程中调用时返回 `SpawnError::MultiRuntime`
+    #[track_caller]
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        if try_current_runtime().is_none() && multi::in_coroutine() {
+            return Err(SpawnError::MultiRuntime);
+        }
+        current_runtime().spawn_with(self, f)
+    }
+}
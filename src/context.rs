@@ -0,0 +1,53 @@
+use std::arch::global_asm;
+
+// options(att_syntax) // 这里你可以修改为 raw | att_syntax 语法
+// options(raw)
+global_asm!(include_str!("switch.s"), options(att_syntax));
+
+/// 协程切换时需要保存/恢复的寄存器，布局必须和 `switch.s` 中的偏移一致
+// 每个寄存器使用固定 8 字节偏移
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct ThreadContext {
+    pub rsp: u64, // 0x00 Stack Pointer 栈指针寄存器，指向当前栈顶位置，每个协程有自己的栈空间，切换时必须保存/恢复，确保协程恢复后能继续使用自己的栈
+    pub r15: u64, // 0x08
+    pub r14: u64, // 0x10
+    pub r13: u64, // 0x18
+    pub r12: u64, // 0x20
+    pub rbx: u64, // 0x28 通用寄存器，常用于存储基地址或计算
+    pub rbp: u64, // 0x30 Base Pointer 基指针寄存器，用于访问栈帧中的局部变量和参数，维护函数调用栈的结构，在调试和栈回溯中特别重要
+    pub thread_ptr: u64, // 0x38 切换后放入 rdi，作为入口函数的第一个参数
+}
+
+unsafe extern "C" {
+    /// 保存当前寄存器到 `old_ctx`，然后从 `new_ctx` 恢复并 `ret` 到新栈顶保存的地址
+    pub unsafe fn switch(old_ctx: *mut ThreadContext, new_ctx: *const ThreadContext);
+}
+
+// 不使用这种方式，rust 函数会对汇编做处理
+// 可能被优化掉或编译器插入清理逻辑（epilogue）
+// #[naked]
+// #[inline(never)]
+// unsafe fn no_use_switch(old_ctx: *mut ThreadContext, new_ctx: *const ThreadContext) {
+//     unsafe {
+//         asm!(
+//             "mov [rdi + 0x00], rsp",
+//             "mov [rdi + 0x08], r15",
+//             "mov [rdi + 0x10], r14",
+//             "mov [rdi + 0x18], r13",
+//             "mov [rdi + 0x20], r12",
+//             "mov [rdi + 0x28], rbx",
+//             "mov [rdi + 0x30], rbp",
+//             "mov rsp, [rsi + 0x00]",
+//             "mov r15, [rsi + 0x08]",
+//             "mov r14, [rsi + 0x10]",
+//             "mov r13, [rsi + 0x18]",
+//             "mov r12, [rsi + 0x20]",
+//             "mov rbx, [rsi + 0x28]",
+//             "mov rbp, [rsi + 0x30]",
+//             "ret",
+//             in("rdi") old_ctx,
+//             in("rsi") new_ctx
+//         );
+//     }
+// }
@@ -0,0 +1,102 @@
+//! 等待 fd 就绪：当前协程阻塞，其他协程继续运行，fd 就绪后由 epoll 唤醒
+//!
+//! fd 需要设置为非阻塞，先尝试 I/O，返回 `WouldBlock` 时再等待：
+//!
+//! ```ignore
+//! loop {
+//!     match socket.read(&mut buf) {
+//!         Err(e) if e.kind() == io::ErrorKind::WouldBlock => rustcoro::io::wait_readable(fd)?,
+//!         result => break result,
+//!     }
+//! }
+//! ```
+//!
+//! 开启 `io-uring` feature 之后还可以用 `read`、`write`、`read_at`、`write_at`、`accept`、`connect`：
+//! 操作作为 SQE 提交给 io_uring，当前协程等待完成事件，fd 不需要是非阻塞的。
+//! epoll 没法让普通文件的读写不阻塞，读写文件时应该用这些函数。
+
+use std::io;
+#[cfg(feature = "io-uring")]
+use std::net::SocketAddr;
+use std::os::fd::RawFd;
+#[cfg(feature = "io-uring")]
+use std::os::fd::{FromRawFd, OwnedFd};
+
+#[cfg(feature = "io-uring")]
+use io_uring::{opcode, types};
+
+use crate::reactor::Direction;
+use crate::runtime::current_runtime;
+
+/// 阻塞当前协程直到 fd 可读（或者对端关闭、出错）
+///
+/// 同一个 fd 同时只能有一个协程等待可读，否则返回 `ResourceBusy`
+pub fn wait_readable(fd: RawFd) -> io::Result<()> {
+    current_runtime().t_wait_fd(fd, Direction::Read)
+}
+
+/// 阻塞当前协程直到 fd 可写（或者出错）
+///
+/// 同一个 fd 同时只能有一个协程等待可写，否则返回 `ResourceBusy`
+pub fn wait_writable(fd: RawFd) -> io::Result<()> {
+    current_runtime().t_wait_fd(fd, Direction::Write)
+}
+
+// offset 为 -1 时使用并推进文件的当前位置
+#[cfg(feature = "io-uring")]
+const CURRENT_POSITION: u64 = u64::MAX;
+
+/// 从文件当前位置读，返回读到的字节数
+#[cfg(feature = "io-uring")]
+pub fn read(fd: RawFd, buf: &mut [u8]) -> io::Result<usize> {
+    read_at(fd, buf, CURRENT_POSITION)
+}
+
+/// 写到文件当前位置，返回写入的字节数
+#[cfg(feature = "io-uring")]
+pub fn write(fd: RawFd, buf: &[u8]) -> io::Result<usize> {
+    write_at(fd, buf, CURRENT_POSITION)
+}
+
+/// 从 `offset` 开始读，不改变文件的当前位置
+#[cfg(feature = "io-uring")]
+pub fn read_at(fd: RawFd, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let len = buf.len().min(u32::MAX as usize) as u32;
+    let entry = opcode::Read::new(types::Fd(fd), buf.as_mut_ptr(), len)
+        .offset(offset)
+        .build();
+    // buf 在返回之前一直被借用
+    let n = unsafe { current_runtime().t_submit(entry)? };
+    Ok(n as usize)
+}
+
+/// 从 `offset` 开始写，不改变文件的当前位置
+#[cfg(feature = "io-uring")]
+pub fn write_at(fd: RawFd, buf: &[u8], offset: u64) -> io::Result<usize> {
+    let len = buf.len().min(u32::MAX as usize) as u32;
+    let entry = opcode::Write::new(types::Fd(fd), buf.as_ptr(), len)
+        .offset(offset)
+        .build();
+    let n = unsafe { current_runtime().t_submit(entry)? };
+    Ok(n as usize)
+}
+
+/// 接受监听 socket 上的一个连接，新的 fd 设置了 close-on-exec
+#[cfg(feature = "io-uring")]
+pub fn accept(fd: RawFd) -> io::Result<OwnedFd> {
+    let entry = opcode::Accept::new(types::Fd(fd), std::ptr::null_mut(), std::ptr::null_mut())
+        .flags(libc::SOCK_CLOEXEC)
+        .build();
+    let conn = unsafe { current_runtime().t_submit(entry)? };
+    Ok(unsafe { OwnedFd::from_raw_fd(conn) })
+}
+
+/// 把 socket 连接到 `addr`
+#[cfg(feature = "io-uring")]
+pub fn connect(fd: RawFd, addr: &SocketAddr) -> io::Result<()> {
+    let (storage, len) = crate::net::sockaddr(addr);
+    let entry = opcode::Connect::new(types::Fd(fd), (&raw const storage).cast(), len).build();
+    // storage 在返回之前一直在栈上
+    unsafe { current_runtime().t_submit(entry)? };
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::marker::PhantomData;
+use std::panic;
+
+use crate::runtime::{Cancelled, WaitReason, runtime_of, try_current_runtime};
+
+/// spawn 返回的句柄，`join` 等待协程结束并取回它的返回值
+///
+/// 句柄存活期间协程的槽位不会被复用；丢弃句柄相当于 detach，结果会被直接丢掉。
+/// 句柄只在创建它的 runtime 中有意义，所以不能发送到别的线程；在别的 runtime 上使用会 panic
+pub struct JoinHandle<T> {
+    runtime: u64,
+    id: usize,
+    _marker: PhantomData<(T, *const ())>,
+}
+
+impl<T: 'static> JoinHandle<T> {
+    pub(crate) fn new(runtime: u64, id: usize) -> Self {
+        JoinHandle {
+            runtime,
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 协程所在的线程编号
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// 协程栈的最高水位，见 `Runtime::stack_usage`
+    pub fn stack_usage(&self) -> Option<usize> {
+        runtime_of(self.runtime).stack_usage(self.id)
+    }
+
+    /// 修改协程的优先级，数值越大越优先；协程已经结束的话没有效果
+    pub fn set_priority(&self, priority: u8) {
+        runtime_of(self.runtime).set_priority(self.id, priority);
+    }
+
+    /// 协程错过截止时间的次数，见 `Runtime::missed_deadlines`
+    pub fn missed_deadlines(&self) -> usize {
+        runtime_of(self.runtime).missed_deadlines(self.id)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        runtime_of(self.runtime).is_finished(self.id)
+    }
+
+    /// 取消协程，返回是否成功发出取消请求
+    ///
+    /// 还没开始运行的协程不再运行；已经开始的在下一次恢复运行时展开栈（执行 Drop）后结束。
+    /// 已经结束的协程不能取消
+    pub fn cancel(&self) -> bool {
+        runtime_of(self.runtime).cancel(self.id)
+    }
+
+    /// 阻塞直到协程结束，返回它的结果
+    ///
+    /// 协程 panic 的话，panic 会在调用 join 的地方继续传播；协程被取消的话 join 会 panic
+    pub fn join(self) -> T {
+        let rt = runtime_of(self.runtime);
+        rt.set_waiter(self.id);
+
+        // 在 park 中被取消时 self 随栈展开被丢弃，相当于 detach
+        let result = loop {
+            if let Some(result) = rt.take_result(self.id) {
+                break result;
+            }
+            rt.t_park(WaitReason::Join(self.id));
+        };
+        let id = self.id;
+        std::mem::forget(self);
+
+        match result {
+            Ok(result) => *result.downcast::<T>().expect("join result type mismatch"),
+            Err(payload) if payload.is::<Cancelled>() => {
+                panic!("joined coroutine {} was cancelled", id)
+            }
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        // runtime 已经被丢弃、或者当前注册的是别的 runtime 的话，不能碰它的协程
+        if let Some(rt) = try_current_runtime()
+            && rt.id() == self.runtime
+        {
+            rt.detach(self.id);
+        }
+    }
+}
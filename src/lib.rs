@@ -0,0 +1,30 @@
+mod blocking;
+mod builder;
+pub mod context;
+pub mod io;
+mod join;
+pub mod multi;
+pub mod net;
+mod park;
+mod pool;
+mod queue;
+mod reactor;
+mod runtime;
+pub mod scheduler;
+mod signal;
+mod stack;
+pub mod time;
+mod timer;
+#[cfg(feature = "io-uring")]
+mod uring;
+
+pub use blocking::spawn_blocking;
+pub use builder::Builder;
+pub use join::JoinHandle;
+pub use multi::MultiRuntime;
+pub use park::{Coroutine, current, park, unpark};
+pub use runtime::{
+    RunSummary, Runtime, SpawnError, call_thread, set_deadline, set_priority, spawn, yield_now,
+    yield_to_caller,
+};
+pub use scheduler::Scheduler;
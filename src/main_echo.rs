@@ -0,0 +1,52 @@
+use std::io::{Read, Write};
+
+use rustcoro::Runtime;
+use rustcoro::net::{TcpListener, TcpStream};
+
+const CLIENTS: usize = 4;
+
+// echo 服务器测试：每个连接一个协程，客户端也是同一个 runtime 上的协程
+fn main() {
+    println!("echo server run.");
+    let mut runtime = Runtime::new();
+    runtime.init();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    println!("listening on {}", addr);
+
+    Runtime::spawnf(move || {
+        for _ in 0..CLIENTS {
+            let (mut stream, peer) = listener.accept().unwrap();
+            Runtime::spawnf(move || {
+                let mut buf = [0; 1024];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        println!("connection from {} closed", peer);
+                        break;
+                    }
+                    stream.write_all(&buf[..n]).unwrap();
+                }
+            })
+            .unwrap();
+        }
+    })
+    .unwrap();
+
+    for id in 0..CLIENTS {
+        Runtime::spawnf(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let message = format!("hello from client {}", id);
+            stream.write_all(message.as_bytes()).unwrap();
+
+            let mut reply = vec![0; message.len()];
+            stream.read_exact(&mut reply).unwrap();
+            println!("client {} got: {}", id, String::from_utf8(reply).unwrap());
+        })
+        .unwrap();
+    }
+
+    let summary = runtime.run();
+    println!("while finished: {:?}", summary);
+}
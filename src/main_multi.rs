@@ -0,0 +1,38 @@
+use rustcoro::{MultiRuntime, yield_now};
+
+// M:N 调度测试：4 个 worker 线程运行 8 个协程，协程 yield 之后可能在另一个线程上恢复
+fn main() {
+    println!("multi runtime run.");
+    let runtime = MultiRuntime::new(4);
+
+    for id in 1..=8 {
+        // 协程中只有 println!，没有跨 yield_now 的 !Send 状态
+        unsafe {
+            runtime.spawn(move || {
+                for i in 0..3 {
+                    println!(
+                        "thread: {} counter: {} on {:?}",
+                        id,
+                        i,
+                        std::thread::current().id()
+                    );
+                    yield_now();
+                }
+
+                // 在 worker 上也可以继续创建协程
+                MultiRuntime::spawnf(move || {
+                    println!(
+                        "nested task of thread {} on {:?}",
+                        id,
+                        std::thread::current().id()
+                    );
+                })
+                .unwrap();
+            })
+        }
+        .unwrap();
+    }
+
+    let summary = runtime.run();
+    println!("while finished: {:?}", summary);
+}
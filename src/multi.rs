@@ -0,0 +1,336 @@
+//! M:N 调度：N 个 worker OS 线程运行任意多个协程，空闲的 worker 从忙碌的 worker 偷协程
+//!
+//! 和单线程的 `Runtime` 不同，每个 worker 有一个调度上下文（运行在 OS 线程自己的栈上），
+//! 协程 yield 时总是先切回调度上下文，由它把协程放回队列。这样协程的寄存器保存完成之前
+//! 不会出现在任何队列里，其他 worker 偷走它也是安全的。
+//!
+//! worker 上没有单线程 `Runtime`：协程中只能使用 `MultiRuntime::spawnf` 和 `rustcoro::yield_now`。
+//! `rustcoro::spawn`、`Runtime::spawnf`、`Builder::spawn` 返回 `SpawnError::MultiRuntime`
+//! （`spawn` 会 panic），`JoinHandle`、`park`/`unpark`、`time`、`net`、`io`、`spawn_blocking`
+//! 等需要 runtime 的 API 会 panic。
+//!
+//! # 协程会换 OS 线程
+//!
+//! `F: Send` 只保证闭包本身可以发送，协程运行中创建的 `!Send` 状态却会跟着它换线程：
+//! `yield_now` 之前取得的 `thread_local` 引用（比如 `RefCell` 的借用）、`Rc`、`MutexGuard`，
+//! 恢复运行时已经在另一个 worker 上，访问的是别的线程的数据，或者在别的线程上解锁。
+//! 编译器检查不出来，所以 `spawn` 和 `spawnf` 是 `unsafe` 的：
+//! 调用者保证协程不会把这样的状态保留到 `yield_now` 之后。
+//!
+//! ```ignore
+//! let rt = MultiRuntime::new(4);
+//! // 协程中没有跨 yield_now 的 !Send 状态
+//! unsafe {
+//!     rt.spawn(|| {
+//!         MultiRuntime::spawnf(|| println!("spawned from a worker")).unwrap();
+//!         rustcoro::yield_now(); // 可能在另一个 worker 上恢复
+//!     })?;
+//! }
+//! let summary = rt.run();
+//! ```
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::context::{ThreadContext, switch};
+use crate::runtime::{DEFAULT_STACK_SIZE, RunSummary, SpawnError};
+use crate::signal::install_overflow_handler;
+use crate::stack::Stack;
+
+thread_local! {
+    static WORKER: Cell<*mut Worker> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+struct Coroutine {
+    id: usize,
+    stack: Stack,
+    ctx: ThreadContext,
+    task: Option<Box<dyn FnOnce() + Send>>,
+    finished: bool,
+    panicked: bool,
+}
+
+// 协程同一时间只被一个 worker 持有，栈内存只属于它自己
+unsafe impl Send for Coroutine {}
+
+struct Shared {
+    queues: Vec<Mutex<VecDeque<Box<Coroutine>>>>, // 每个 worker 一个本地队列
+    injector: Mutex<VecDeque<Box<Coroutine>>>,    // run 之前或者从 worker 以外 spawn 的协程
+    active: AtomicUsize,                          // 还没结束的协程数量
+    next_id: AtomicUsize,
+    idle: Mutex<usize>, // 在 wakeup 上等待的 worker 数量
+    wakeup: Condvar,
+}
+
+struct Worker {
+    index: usize,
+    shared: *const Shared,
+    ctx: ThreadContext, // 调度上下文
+    current: Option<Box<Coroutine>>,
+    stats: RunSummary,
+}
+
+/// M:N 运行时，`run` 时启动 N 个 worker 线程
+pub struct MultiRuntime {
+    shared: Shared,
+}
+
+impl MultiRuntime {
+    pub fn new(workers: usize) -> Self {
+        assert!(workers > 0, "MultiRuntime needs at least one worker");
+        MultiRuntime {
+            shared: Shared {
+                queues: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+                injector: Mutex::new(VecDeque::new()),
+                active: AtomicUsize::new(0),
+                next_id: AtomicUsize::new(1),
+                idle: Mutex::new(0),
+                wakeup: Condvar::new(),
+            },
+        }
+    }
+
+    /// 在 `run` 之前添加协程
+    ///
+    /// # Safety
+    ///
+    /// 协程每次 `yield_now` 之后都可能在另一个 OS 线程上恢复。`f` 运行时不能把 `!Send` 的状态
+    /// （`thread_local` 的引用、`Rc`、`MutexGuard` 等）保留到 `yield_now` 之后，见模块文档
+    pub unsafe fn spawn<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), SpawnError> {
+        let co = self.shared.coroutine(f)?;
+        self.shared.injector.lock().unwrap().push_back(co);
+        self.shared.notify();
+        Ok(())
+    }
+
+    /// 在 worker 上运行的协程中创建新协程，放进当前 worker 的本地队列
+    ///
+    /// # Safety
+    ///
+    /// 和 `spawn` 相同，`f` 不能把 `!Send` 的状态保留到 `yield_now` 之后
+    pub unsafe fn spawnf<F: FnOnce() + Send + 'static>(f: F) -> Result<(), SpawnError> {
+        let worker = current_worker().expect("MultiRuntime::spawnf called outside of a worker");
+        let shared = worker.shared();
+        let co = shared.coroutine(f)?;
+        shared.queues[worker.index].lock().unwrap().push_back(co);
+        shared.notify();
+        Ok(())
+    }
+
+    /// 启动 worker 线程，所有协程结束后返回
+    pub fn run(&self) -> RunSummary {
+        let shared = &self.shared;
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..shared.queues.len())
+                .map(|index| scope.spawn(move || Worker::new(index, shared).run()))
+                .collect();
+
+            workers.into_iter().map(|w| w.join().unwrap()).fold(
+                RunSummary::default(),
+                |total, stats| RunSummary {
+                    tasks: total.tasks + stats.tasks,
+                    switches: total.switches + stats.switches,
+                    panics: total.panics + stats.panics,
+                },
+            )
+        })
+    }
+}
+
+impl Shared {
+    fn coroutine<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<Box<Coroutine>, SpawnError> {
+        let stack = Stack::new(DEFAULT_STACK_SIZE).map_err(SpawnError::Stack)?;
+        let mut co = Box::new(Coroutine {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            stack,
+            ctx: ThreadContext::default(),
+            task: Some(Box::new(f)),
+            finished: false,
+            panicked: false,
+        });
+
+        // 栈布局和 Runtime::spawnf 相同，switch 通过 ret 跳到 call，rdi 是协程地址
+        unsafe {
+            let s_aligned = (co.stack.top() as usize & !15) as *mut u8;
+            std::ptr::write(s_aligned.offset(-8) as *mut u64, 0);
+            std::ptr::write(s_aligned.offset(-16) as *mut u64, call as *const () as u64);
+            co.ctx.rsp = s_aligned.offset(-16) as u64;
+        }
+        co.ctx.thread_ptr = &*co as *const Coroutine as u64;
+
+        self.active.fetch_add(1, Ordering::SeqCst);
+        Ok(co)
+    }
+
+    // 有空闲的 worker 就唤醒一个。在 idle 锁下通知：worker 持有锁检查队列之后才开始等待，
+    // 所以不会在检查和等待之间漏掉通知
+    fn notify(&self) {
+        let idle = self.idle.lock().unwrap();
+        if *idle > 0 {
+            self.wakeup.notify_one();
+        }
+    }
+
+    // 偷另一个队列后一半的协程，返回其中一个，剩下的放进自己的队列
+    fn steal(&self, thief: usize) -> Option<Box<Coroutine>> {
+        let n = self.queues.len();
+        for offset in 1..n {
+            let victim = (thief + offset) % n;
+            let mut stolen = {
+                let mut queue = self.queues[victim].lock().unwrap();
+                let len = queue.len();
+                queue.split_off(len - len.div_ceil(2))
+            };
+            if let Some(co) = stolen.pop_front() {
+                self.queues[thief].lock().unwrap().append(&mut stolen);
+                return Some(co);
+            }
+        }
+        None
+    }
+}
+
+impl Worker {
+    fn new(index: usize, shared: &Shared) -> Self {
+        Worker {
+            index,
+            shared,
+            ctx: ThreadContext::default(),
+            current: None,
+            stats: RunSummary::default(),
+        }
+    }
+
+    fn shared(&self) -> &Shared {
+        unsafe { &*self.shared }
+    }
+
+    fn run(mut self) -> RunSummary {
+        WORKER.with(|w| w.set(&mut self));
+        install_overflow_handler();
+
+        loop {
+            if let Some(co) = self.next() {
+                self.resume(co);
+                continue;
+            }
+
+            let shared = unsafe { &*self.shared };
+            let mut idle = shared.idle.lock().unwrap();
+            if shared.active.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            // 持有锁再检查一次，之后放进队列的协程一定会通知到这里
+            if let Some(co) = self.next() {
+                drop(idle);
+                self.resume(co);
+                continue;
+            }
+            *idle += 1;
+            idle = shared.wakeup.wait(idle).unwrap();
+            *idle -= 1;
+        }
+
+        WORKER.with(|w| w.set(std::ptr::null_mut()));
+        self.stats
+    }
+
+    // 本地队列 -> 全局队列 -> 偷其他 worker
+    fn next(&self) -> Option<Box<Coroutine>> {
+        let shared = self.shared();
+        if let Some(co) = shared.queues[self.index].lock().unwrap().pop_front() {
+            return Some(co);
+        }
+        if let Some(co) = shared.injector.lock().unwrap().pop_front() {
+            return Some(co);
+        }
+        shared.steal(self.index)
+    }
+
+    fn resume(&mut self, co: Box<Coroutine>) {
+        let new: *const ThreadContext = &co.ctx;
+        self.current = Some(co);
+        self.stats.switches += 1;
+        unsafe {
+            switch(&mut self.ctx, new);
+        }
+
+        // 协程已经切回调度上下文，寄存器保存完成，现在可以放回队列给任何 worker
+        let co = self.current.take().unwrap();
+        if co.finished {
+            self.stats.tasks += 1;
+            if co.panicked {
+                self.stats.panics += 1;
+            }
+            drop(co); // 在调度上下文的栈上释放协程栈
+            // 最后一个协程结束时唤醒所有还在等待的 worker，让它们退出
+            let shared = self.shared();
+            if shared.active.fetch_sub(1, Ordering::SeqCst) == 1 {
+                let _idle = shared.idle.lock().unwrap();
+                shared.wakeup.notify_all();
+            }
+        } else {
+            let mut queue = self.shared().queues[self.index].lock().unwrap();
+            queue.push_back(co);
+            // 自己接下来只会取走一个，多出来的交给空闲的 worker 偷
+            let surplus = queue.len() > 1;
+            drop(queue);
+            if surplus {
+                self.shared().notify();
+            }
+        }
+    }
+}
+
+// 不内联：协程可能在另一个 OS 线程上恢复，每次都要重新读线程局部变量
+#[inline(never)]
+fn current_worker() -> Option<&'static mut Worker> {
+    unsafe { WORKER.with(Cell::get).as_mut() }
+}
+
+// 当前 OS 线程是不是 worker，并且正在运行协程
+pub(crate) fn in_coroutine() -> bool {
+    current_worker().is_some_and(|w| w.current.is_some())
+}
+
+// 切回调度上下文，之后可能被任何一个 worker 恢复
+pub(crate) fn yield_now() {
+    let worker = current_worker().expect("not running on a MultiRuntime worker");
+    let co = worker
+        .current
+        .as_mut()
+        .expect("no coroutine running on this worker");
+    let old: *mut ThreadContext = &mut co.ctx;
+    unsafe {
+        switch(old, &worker.ctx);
+    }
+}
+
+// 在信号处理函数中调用，检查当前 worker 正在运行的协程是否栈溢出
+pub(crate) fn overflowed_coroutine(addr: usize) -> Option<(usize, usize)> {
+    let worker = unsafe { WORKER.with(Cell::get).as_ref()? };
+    let co = worker.current.as_ref()?;
+    co.stack
+        .guard_contains(addr)
+        .then(|| (co.id, co.stack.size()))
+}
+
+extern "C" fn call(co: u64) -> ! {
+    let co = unsafe { &mut *(co as *mut Coroutine) };
+
+    if let Some(f) = co.task.take() {
+        co.panicked = panic::catch_unwind(AssertUnwindSafe(f)).is_err();
+    }
+    co.finished = true;
+
+    // 重新读取 worker：协程可能已经被偷到别的线程
+    let worker = current_worker().unwrap();
+    unsafe {
+        switch(&mut co.ctx, &worker.ctx);
+    }
+    unreachable!("finished coroutine resumed");
+}
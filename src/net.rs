@@ -0,0 +1,660 @@
+//! 协程版的网络类型：socket 设置为非阻塞，`WouldBlock` 时当前协程等待 fd 就绪，
+//! 其他协程继续运行。在协程看来和 std 的阻塞 API 一样
+//!
+//! ```ignore
+//! let listener = TcpListener::bind("127.0.0.1:8080")?;
+//! loop {
+//!     let (mut stream, _) = listener.accept()?;
+//!     rustcoro::spawn(move || io::copy(&mut &stream, &mut &stream));
+//! }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net as unix;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::io::{wait_readable, wait_writable};
+use crate::time;
+
+// 重复执行非阻塞操作，WouldBlock 时等待 fd 就绪
+fn retry<T>(
+    fd: RawFd,
+    wait: fn(RawFd) -> io::Result<()>,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    loop {
+        match op() {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => wait(fd)?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }
+}
+
+// 对 ToSocketAddrs 解析出的每个地址依次尝试，返回第一个成功的结果或者最后一个错误
+fn each_addr<A: ToSocketAddrs, T>(
+    addr: A,
+    mut f: impl FnMut(&SocketAddr) -> io::Result<T>,
+) -> io::Result<T> {
+    let mut last = None;
+    for addr in addr.to_socket_addrs()? {
+        match f(&addr) {
+            Ok(value) => return Ok(value),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(last.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    }))
+}
+
+// 一条消息最多接收多少个 fd，超过的部分被内核关闭
+const MAX_FDS: usize = 32;
+
+// sendmsg 带上 SCM_RIGHTS 控制消息，把 fds 复制到对端进程
+fn send_with_fds(fd: RawFd, buf: &[u8], fds: &[BorrowedFd<'_>]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    let payload = mem::size_of_val(fds);
+    let space = unsafe { libc::CMSG_SPACE(payload as u32) } as usize;
+    let mut control = vec![0_u64; space.div_ceil(8)]; // cmsghdr 需要按 8 字节对齐
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    if !fds.is_empty() {
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = space;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(payload as u32) as usize;
+            let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+            for (i, fd) in fds.iter().enumerate() {
+                data.add(i).write_unaligned(fd.as_raw_fd());
+            }
+        }
+    }
+
+    let n = unsafe { libc::sendmsg(fd, &msg, libc::MSG_NOSIGNAL) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+// recvmsg 并取出 SCM_RIGHTS 中的 fd，收到的 fd 设置了 close-on-exec
+fn recv_with_fds(fd: RawFd, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let space = unsafe { libc::CMSG_SPACE((MAX_FDS * mem::size_of::<RawFd>()) as u32) } as usize;
+    let mut control = vec![0_u64; space.div_ceil(8)];
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = space;
+
+    let n = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                let len = (*cmsg).cmsg_len - libc::CMSG_LEN(0) as usize;
+                for i in 0..len / mem::size_of::<RawFd>() {
+                    fds.push(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok(n as usize)
+}
+
+// Unix socket 的监听队列满时 connect 返回 EAGAIN，也没有 fd 事件可以等，隔一会儿重试
+const CONNECT_RETRY: Duration = Duration::from_millis(1);
+
+fn sockaddr_un(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.contains(&0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "paths must not contain interior null bytes",
+        ));
+    }
+    // 末尾要留一个 0
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path must be shorter than SUN_LEN",
+        ));
+    }
+    for (dst, &src) in addr.sun_path.iter_mut().zip(bytes) {
+        *dst = src as libc::c_char;
+    }
+
+    let offset = mem::offset_of!(libc::sockaddr_un, sun_path);
+    let len = if bytes.is_empty() {
+        offset
+    } else {
+        offset + bytes.len() + 1
+    };
+    Ok((addr, len as libc::socklen_t))
+}
+
+pub(crate) fn sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sin = unsafe { &mut *(&raw mut storage).cast::<libc::sockaddr_in>() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sin6 = unsafe { &mut *(&raw mut storage).cast::<libc::sockaddr_in6>() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// 监听 TCP 连接，`accept` 等待时让出执行权
+#[derive(Debug)]
+pub struct TcpListener {
+    inner: net::TcpListener,
+}
+
+impl TcpListener {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        let inner = net::TcpListener::bind(addr)?;
+        inner.set_nonblocking(true)?;
+        Ok(TcpListener { inner })
+    }
+
+    /// 等待新的连接
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = retry(self.as_raw_fd(), wait_readable, || self.inner.accept())?;
+        stream.set_nonblocking(true)?;
+        Ok((TcpStream { inner: stream }, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// TCP 连接，读写在 `WouldBlock` 时让出执行权
+///
+/// `&TcpStream` 也实现了 `Read`/`Write`，一个协程读、另一个协程写同一个连接是可以的，
+/// 但同一个方向同时只能有一个协程在等待
+#[derive(Debug)]
+pub struct TcpStream {
+    inner: net::TcpStream,
+}
+
+impl TcpStream {
+    /// 非阻塞地发起连接，连接建立之前当前协程等待
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        each_addr(addr, Self::connect_addr)
+    }
+
+    fn connect_addr(addr: &SocketAddr) -> io::Result<TcpStream> {
+        let family = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let fd = unsafe {
+            libc::socket(
+                family,
+                libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let (storage, len) = sockaddr(addr);
+        let result = unsafe { libc::connect(fd, (&raw const storage).cast(), len) };
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINPROGRESS) {
+                return Err(err);
+            }
+
+            // 连接完成（成功或者失败）时 socket 变为可写，结果在 SO_ERROR 中
+            wait_writable(fd)?;
+            let mut error: libc::c_int = 0;
+            let mut error_len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+            let result = unsafe {
+                libc::getsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_ERROR,
+                    (&raw mut error).cast(),
+                    &mut error_len,
+                )
+            };
+            if result < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if error != 0 {
+                return Err(io::Error::from_raw_os_error(error));
+            }
+        }
+
+        Ok(TcpStream {
+            inner: net::TcpStream::from(socket),
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.inner.set_nodelay(nodelay)
+    }
+}
+
+impl Read for &TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || (&self.inner).read(buf))
+    }
+}
+
+impl Write for &TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || (&self.inner).write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// UDP socket，收发在 `WouldBlock` 时让出执行权
+#[derive(Debug)]
+pub struct UdpSocket {
+    inner: net::UdpSocket,
+}
+
+impl UdpSocket {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
+        let inner = net::UdpSocket::bind(addr)?;
+        inner.set_nonblocking(true)?;
+        Ok(UdpSocket { inner })
+    }
+
+    /// 等待一个数据报，返回长度和发送方地址；缓冲区放不下的部分被丢弃
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        retry(self.as_raw_fd(), wait_readable, || {
+            self.inner.recv_from(buf)
+        })
+    }
+
+    /// 发送一个数据报，发送缓冲区满时等待
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        each_addr(addr, |addr| {
+            retry(self.as_raw_fd(), wait_writable, || {
+                self.inner.send_to(buf, addr)
+            })
+        })
+    }
+
+    /// 设置默认的对端地址，之后可以用 `send`/`recv`
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        self.inner.connect(addr)
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || self.inner.recv(buf))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || self.inner.send(buf))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// 监听 Unix 域流式连接，`accept` 等待时让出执行权
+#[derive(Debug)]
+pub struct UnixListener {
+    inner: unix::UnixListener,
+}
+
+impl UnixListener {
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixListener> {
+        let inner = unix::UnixListener::bind(path)?;
+        inner.set_nonblocking(true)?;
+        Ok(UnixListener { inner })
+    }
+
+    /// 等待新的连接
+    pub fn accept(&self) -> io::Result<(UnixStream, unix::SocketAddr)> {
+        let (stream, addr) = retry(self.as_raw_fd(), wait_readable, || self.inner.accept())?;
+        stream.set_nonblocking(true)?;
+        Ok((UnixStream { inner: stream }, addr))
+    }
+
+    pub fn local_addr(&self) -> io::Result<unix::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// Unix 域流式连接，读写在 `WouldBlock` 时让出执行权，可以用 `SCM_RIGHTS` 传递 fd
+#[derive(Debug)]
+pub struct UnixStream {
+    inner: unix::UnixStream,
+}
+
+impl UnixStream {
+    /// 连接到 `path`，监听队列满时当前协程隔 1ms 重试，直到对端 accept 腾出位置
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<UnixStream> {
+        let (addr, len) = sockaddr_un(path.as_ref())?;
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_UNIX,
+                libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                0,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        loop {
+            if unsafe { libc::connect(fd, (&raw const addr).cast(), len) } == 0 {
+                break;
+            }
+            let err = io::Error::last_os_error();
+            match err.kind() {
+                io::ErrorKind::Interrupted => {}
+                io::ErrorKind::WouldBlock => time::sleep(CONNECT_RETRY),
+                _ => return Err(err),
+            }
+        }
+
+        Ok(UnixStream {
+            inner: unix::UnixStream::from(socket),
+        })
+    }
+
+    /// 一对互相连接的 socket
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = unix::UnixStream::pair()?;
+        a.set_nonblocking(true)?;
+        b.set_nonblocking(true)?;
+        Ok((UnixStream { inner: a }, UnixStream { inner: b }))
+    }
+
+    /// 发送数据和 fd，fd 随第一个字节一起到达对端
+    pub fn send_with_fds(&self, buf: &[u8], fds: &[BorrowedFd<'_>]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || {
+            send_with_fds(self.as_raw_fd(), buf, fds)
+        })
+    }
+
+    /// 接收数据，同时收到的 fd 追加到 `fds`；一次最多接收 32 个 fd
+    pub fn recv_with_fds(&self, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || {
+            recv_with_fds(self.as_raw_fd(), buf, fds)
+        })
+    }
+
+    pub fn peer_addr(&self) -> io::Result<unix::SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> io::Result<unix::SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.inner.shutdown(how)
+    }
+}
+
+impl Read for &UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || (&self.inner).read(buf))
+    }
+}
+
+impl Write for &UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || (&self.inner).write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for UnixStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for UnixStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// Unix 域数据报 socket，收发在 `WouldBlock` 时让出执行权，可以用 `SCM_RIGHTS` 传递 fd
+#[derive(Debug)]
+pub struct UnixDatagram {
+    inner: unix::UnixDatagram,
+}
+
+impl UnixDatagram {
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<UnixDatagram> {
+        Self::new(unix::UnixDatagram::bind(path)?)
+    }
+
+    /// 没有绑定地址的 socket，只能发送或者 connect 之后收发
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        Self::new(unix::UnixDatagram::unbound()?)
+    }
+
+    /// 一对互相连接的 socket
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (a, b) = unix::UnixDatagram::pair()?;
+        Ok((Self::new(a)?, Self::new(b)?))
+    }
+
+    fn new(inner: unix::UnixDatagram) -> io::Result<UnixDatagram> {
+        inner.set_nonblocking(true)?;
+        Ok(UnixDatagram { inner })
+    }
+
+    /// 设置默认的对端地址，之后可以用 `send`/`recv`
+    pub fn connect<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.inner.connect(path)
+    }
+
+    /// 等待一个数据报，返回长度和发送方地址
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, unix::SocketAddr)> {
+        retry(self.as_raw_fd(), wait_readable, || {
+            self.inner.recv_from(buf)
+        })
+    }
+
+    /// 发送一个数据报，对端接收队列满时等待
+    pub fn send_to<P: AsRef<Path>>(&self, buf: &[u8], path: P) -> io::Result<usize> {
+        let path = path.as_ref();
+        retry(self.as_raw_fd(), wait_writable, || {
+            self.inner.send_to(buf, path)
+        })
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || self.inner.recv(buf))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || self.inner.send(buf))
+    }
+
+    /// 向已连接的对端发送数据报和 fd
+    pub fn send_with_fds(&self, buf: &[u8], fds: &[BorrowedFd<'_>]) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_writable, || {
+            send_with_fds(self.as_raw_fd(), buf, fds)
+        })
+    }
+
+    /// 接收数据报，同时收到的 fd 追加到 `fds`；一次最多接收 32 个 fd
+    pub fn recv_with_fds(&self, buf: &mut [u8], fds: &mut Vec<OwnedFd>) -> io::Result<usize> {
+        retry(self.as_raw_fd(), wait_readable, || {
+            recv_with_fds(self.as_raw_fd(), buf, fds)
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<unix::SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<unix::SocketAddr> {
+        self.inner.peer_addr()
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixDatagram {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
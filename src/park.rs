@@ -0,0 +1,62 @@
+//! 协程的 park/unpark：当前协程阻塞，直到别的协程用它的句柄唤醒
+//!
+//! ```ignore
+//! let me = rustcoro::current();
+//! rustcoro::spawn(move || rustcoro::unpark(me));
+//! rustcoro::park();
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::runtime::{WaitReason, current_runtime};
+
+/// 协程句柄，可以复制后交给别的协程用来 `unpark`
+///
+/// 句柄带着协程槽位的 generation，协程结束、槽位被新任务复用后旧句柄不会唤醒新任务；
+/// 同样，在别的 runtime 上 unpark 什么都不做。
+/// 句柄只在协程所在的 OS 线程上有意义，不能发送到别的线程（比如 `spawn_blocking` 的线程池）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coroutine {
+    runtime: u64,
+    id: usize,
+    generation: u64,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Coroutine {
+    /// 协程所在的线程编号，base thread 是 0
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn unpark(&self) {
+        let rt = current_runtime();
+        if rt.id() == self.runtime {
+            rt.unpark(self.id, self.generation);
+        }
+    }
+}
+
+/// 当前协程的句柄
+pub fn current() -> Coroutine {
+    let rt = current_runtime();
+    let (id, generation) = rt.current_coroutine();
+    Coroutine {
+        runtime: rt.id(),
+        id,
+        generation,
+        _not_send: PhantomData,
+    }
+}
+
+/// 阻塞当前协程直到被 `unpark`，之前已经有 unpark 的话立即返回
+///
+/// 和 `std::thread::park` 一样可能提前返回，调用者需要在循环中检查等待的条件
+pub fn park() {
+    current_runtime().t_park(WaitReason::Park);
+}
+
+/// 唤醒 park 中的协程，协程还没 park 的话它下一次 park 立即返回
+pub fn unpark(handle: Coroutine) {
+    handle.unpark();
+}
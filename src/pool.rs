@@ -0,0 +1,87 @@
+use std::io;
+
+use crate::stack::{Stack, round_size};
+
+pub(crate) const DEFAULT_MAX_POOLED_STACKS: usize = 64;
+
+/// 已经结束的协程留下的栈，新的 spawn 优先复用这里的栈
+///
+/// 放回池子的栈会先 `MADV_DONTNEED` 归还物理内存，只保留虚拟地址映射；
+/// 池子满了以后多出来的栈直接 munmap
+pub(crate) struct StackPool {
+    stacks: Vec<Stack>,
+    max: usize,
+}
+
+impl StackPool {
+    pub(crate) fn new(max: usize) -> Self {
+        StackPool {
+            stacks: Vec::new(),
+            max,
+        }
+    }
+
+    pub(crate) fn set_max(&mut self, max: usize) {
+        self.max = max;
+        self.stacks.truncate(max);
+    }
+
+    /// 取一个可用大小正好是 `size`（取整到页之后）的栈，没有就新分配
+    pub(crate) fn get(&mut self, size: usize) -> io::Result<Stack> {
+        let size = round_size(size);
+        match self.stacks.iter().rposition(|s| s.size() == size) {
+            Some(pos) => Ok(self.stacks.swap_remove(pos)),
+            None => Stack::new(size),
+        }
+    }
+
+    pub(crate) fn put(&mut self, mut stack: Stack) {
+        if self.stacks.len() < self.max {
+            stack.release();
+            self.stacks.push(stack);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stack::page_size;
+
+    #[test]
+    fn reuses_stacks_of_the_same_size() {
+        let mut pool = StackPool::new(4);
+        let small = pool.get(16 * 1024).unwrap();
+        let large = pool.get(64 * 1024).unwrap();
+        let (small_top, large_top) = (small.top(), large.top());
+        pool.put(small);
+        pool.put(large);
+
+        // 大小不同的不复用，取整到页之后相同的复用
+        let other = pool.get(32 * 1024).unwrap();
+        assert!(other.top() != small_top && other.top() != large_top);
+        let small = pool.get(16 * 1024 - page_size() + 1).unwrap();
+        assert_eq!(small.top(), small_top);
+        assert_eq!(pool.get(64 * 1024).unwrap().top(), large_top);
+        assert!(pool.stacks.is_empty());
+    }
+
+    #[test]
+    fn keeps_at_most_max_stacks() {
+        let mut pool = StackPool::new(3);
+        let stacks: Vec<_> = (0..5).map(|_| pool.get(16 * 1024).unwrap()).collect();
+        for stack in stacks {
+            pool.put(stack);
+        }
+        assert_eq!(pool.stacks.len(), 3);
+
+        // 调低上限时多出来的栈直接释放
+        pool.set_max(1);
+        assert_eq!(pool.stacks.len(), 1);
+        pool.put(Stack::new(16 * 1024).unwrap());
+        assert_eq!(pool.stacks.len(), 1);
+
+        pool.set_max(0);
+        assert!(pool.stacks.is_empty());
+    }
+}
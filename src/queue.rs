@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+/// 就绪队列：FIFO 加一个 LIFO 的 `next` 槽，入队、出队、移除都是 O(1)
+///
+/// 刚被唤醒的线程放进 `next` 槽，下一次调度优先运行它；被挤出来的线程回到 FIFO 队尾。
+/// 移除是惰性的：每次入队都给线程一个新的 ticket，队列里 ticket 过期的记录出队时直接跳过。
+#[derive(Default)]
+pub(crate) struct ReadyQueue {
+    fifo: VecDeque<(usize, u64)>, // (线程编号, 入队时的 ticket)
+    next: Option<usize>,
+    tickets: Vec<u64>, // 每个线程当前有效的 ticket
+}
+
+impl ReadyQueue {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    // 让这个线程之前的入队记录全部失效，包括 `next` 槽
+    fn bump(&mut self, id: usize) -> u64 {
+        if self.next == Some(id) {
+            self.next = None;
+        }
+        if id >= self.tickets.len() {
+            self.tickets.resize(id + 1, 0);
+        }
+        self.tickets[id] += 1;
+        self.tickets[id]
+    }
+
+    /// 放到 FIFO 队尾
+    pub(crate) fn push(&mut self, id: usize) {
+        let ticket = self.bump(id);
+        self.fifo.push_back((id, ticket));
+    }
+
+    /// 放进 `next` 槽，原来在槽里的线程回到 FIFO 队尾
+    pub(crate) fn push_next(&mut self, id: usize) {
+        self.bump(id);
+        if let Some(prev) = self.next.replace(id) {
+            self.push(prev);
+        }
+    }
+
+    /// 从队列中移除，不在队列中也没关系
+    pub(crate) fn remove(&mut self, id: usize) {
+        self.bump(id);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<usize> {
+        if let Some(id) = self.next.take() {
+            return Some(id);
+        }
+        while let Some((id, ticket)) = self.fifo.pop_front() {
+            if self.tickets[id] == ticket {
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut ReadyQueue) -> Vec<usize> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn fifo_order() {
+        let mut queue = ReadyQueue::new();
+        for id in [3, 1, 2] {
+            queue.push(id);
+        }
+        assert_eq!(drain(&mut queue), [3, 1, 2]);
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn next_slot_runs_first_and_displaced_goes_to_back() {
+        let mut queue = ReadyQueue::new();
+        queue.push(1);
+        queue.push(2);
+        queue.push_next(3);
+        queue.push_next(4); // 3 被挤到队尾
+        assert_eq!(drain(&mut queue), [4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn remove_skips_stale_entries() {
+        let mut queue = ReadyQueue::new();
+        queue.push(1);
+        queue.push(2);
+        queue.push_next(3);
+        queue.remove(1);
+        queue.remove(3);
+        queue.remove(7); // 不在队列中
+        assert_eq!(drain(&mut queue), [2]);
+    }
+
+    #[test]
+    fn requeue_keeps_only_latest_position() {
+        let mut queue = ReadyQueue::new();
+        queue.push(1);
+        queue.push(2);
+        queue.push(1);
+        assert_eq!(drain(&mut queue), [2, 1]);
+
+        // push_next 之后又 push：旧的 next 槽记录失效
+        queue.push_next(5);
+        queue.push(6);
+        queue.push(5);
+        assert_eq!(drain(&mut queue), [6, 5]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+use crate::runtime::Waiter;
+
+// 一次 epoll_wait 最多取多少个事件
+const MAX_EVENTS: usize = 64;
+
+// watch 登记的通知 fd 的事件标记，和普通 fd 区分
+const NOTIFY: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+#[derive(Default)]
+struct Registration {
+    reader: Option<Waiter>,
+    writer: Option<Waiter>,
+    added: bool, // fd 已经在 epoll 集合中（EPOLLONESHOT 触发后仍然在，只是不再报告事件）
+}
+
+impl Registration {
+    fn interest(&self) -> u32 {
+        let mut events = libc::EPOLLONESHOT as u32;
+        if self.reader.is_some() {
+            events |= libc::EPOLLIN as u32 | libc::EPOLLRDHUP as u32;
+        }
+        if self.writer.is_some() {
+            events |= libc::EPOLLOUT as u32;
+        }
+        events
+    }
+
+    fn slot(&mut self, direction: Direction) -> &mut Option<Waiter> {
+        match direction {
+            Direction::Read => &mut self.reader,
+            Direction::Write => &mut self.writer,
+        }
+    }
+}
+
+/// epoll 反应器：记录哪个线程在等哪个 fd，`poll` 返回 fd 就绪的线程
+///
+/// 每个 fd 用 EPOLLONESHOT 注册，事件触发后由等待者自己重试 I/O，需要的话再次等待
+pub(crate) struct Reactor {
+    epoll: Option<OwnedFd>, // 第一次等待 fd 时创建
+    fds: HashMap<RawFd, Registration>,
+    waiters: usize,
+    events: Vec<libc::epoll_event>,
+}
+
+impl Reactor {
+    pub(crate) fn new() -> Self {
+        Reactor {
+            epoll: None,
+            fds: HashMap::new(),
+            waiters: 0,
+            events: Vec::with_capacity(MAX_EVENTS),
+        }
+    }
+
+    /// 正在等待 fd 的线程数量
+    pub(crate) fn waiters(&self) -> usize {
+        self.waiters
+    }
+
+    fn epoll(&mut self) -> io::Result<RawFd> {
+        if let Some(epoll) = &self.epoll {
+            return Ok(epoll.as_raw_fd());
+        }
+        let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(fd) };
+        Ok(self.epoll.insert(epoll).as_raw_fd())
+    }
+
+    /// 登记 waiter 等待 fd 可读或可写，同一个方向同时只能有一个等待者
+    pub(crate) fn register(
+        &mut self,
+        fd: RawFd,
+        direction: Direction,
+        waiter: Waiter,
+    ) -> io::Result<()> {
+        let epoll = self.epoll()?;
+        let registration = self.fds.entry(fd).or_default();
+        if registration.slot(direction).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                "another coroutine is already waiting on this fd",
+            ));
+        }
+        *registration.slot(direction) = Some(waiter);
+
+        let mut event = libc::epoll_event {
+            events: registration.interest(),
+            u64: fd as u64,
+        };
+        // fd 关闭后内核会自动把它移出集合，编号可能已经被新的 fd 复用：MOD 和 ADD 互相兜底
+        let (first, fallback, missing) = if registration.added {
+            (libc::EPOLL_CTL_MOD, libc::EPOLL_CTL_ADD, libc::ENOENT)
+        } else {
+            (libc::EPOLL_CTL_ADD, libc::EPOLL_CTL_MOD, libc::EEXIST)
+        };
+        let mut result = unsafe { libc::epoll_ctl(epoll, first, fd, &mut event) };
+        if result < 0 && io::Error::last_os_error().raw_os_error() == Some(missing) {
+            result = unsafe { libc::epoll_ctl(epoll, fallback, fd, &mut event) };
+        }
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            *registration.slot(direction) = None;
+            return Err(err);
+        }
+
+        registration.added = true;
+        self.waiters += 1;
+        Ok(())
+    }
+
+    /// `fd` 可读时让 `poll` 返回，水平触发，一直有效；就绪的 fd 由调用者自己处理
+    pub(crate) fn watch(&mut self, fd: RawFd) -> io::Result<()> {
+        let epoll = self.epoll()?;
+        let mut event = libc::epoll_event {
+            events: libc::EPOLLIN as u32,
+            u64: NOTIFY,
+        };
+        if unsafe { libc::epoll_ctl(epoll, libc::EPOLL_CTL_ADD, fd, &mut event) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// waiter 是否还在等待 fd（事件还没有到达）
+    pub(crate) fn is_waiting(&self, fd: RawFd, direction: Direction, waiter: Waiter) -> bool {
+        self.fds.get(&fd).is_some_and(|r| match direction {
+            Direction::Read => r.reader == Some(waiter),
+            Direction::Write => r.writer == Some(waiter),
+        })
+    }
+
+    /// 撤销等待，比如等待的协程被取消；已经不在等待的话什么都不做
+    pub(crate) fn cancel(&mut self, fd: RawFd, direction: Direction, waiter: Waiter) {
+        if let Some(registration) = self.fds.get_mut(&fd)
+            && *registration.slot(direction) == Some(waiter)
+        {
+            *registration.slot(direction) = None;
+            self.waiters -= 1;
+        }
+    }
+
+    /// 等待事件，`timeout` 为 `None` 时一直等；返回 fd 就绪的等待者
+    pub(crate) fn poll(&mut self, timeout: Option<Duration>) -> Vec<Waiter> {
+        let mut woken = Vec::new();
+        let Some(epoll) = self.epoll.as_ref().map(AsRawFd::as_raw_fd) else {
+            return woken;
+        };
+
+        // 向上取整到毫秒，避免定时器到期前反复空转
+        let timeout = match timeout {
+            Some(timeout) => {
+                let ms = timeout.as_millis()
+                    + u128::from(!timeout.subsec_nanos().is_multiple_of(1_000_000));
+                ms.min(libc::c_int::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+        let n = unsafe {
+            libc::epoll_wait(
+                epoll,
+                self.events.as_mut_ptr(),
+                MAX_EVENTS as libc::c_int,
+                timeout,
+            )
+        };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            assert_eq!(
+                err.kind(),
+                io::ErrorKind::Interrupted,
+                "epoll_wait failed: {}",
+                err
+            );
+            return woken;
+        }
+        unsafe { self.events.set_len(n as usize) };
+
+        let closed = (libc::EPOLLHUP | libc::EPOLLERR) as u32;
+        for i in 0..self.events.len() {
+            let event = self.events[i];
+            if event.u64 == NOTIFY {
+                continue;
+            }
+            let (flags, fd) = (event.events, event.u64 as RawFd);
+            let Some(registration) = self.fds.get_mut(&fd) else {
+                continue;
+            };
+            if flags & (libc::EPOLLIN as u32 | libc::EPOLLRDHUP as u32 | closed) != 0 {
+                woken.extend(registration.reader.take());
+            }
+            if flags & (libc::EPOLLOUT as u32 | closed) != 0 {
+                woken.extend(registration.writer.take());
+            }
+
+            // ONESHOT 已经关闭了这个 fd 的通知，另一个方向还有人等的话重新打开
+            if registration.reader.is_some() || registration.writer.is_some() {
+                let mut rearm = libc::epoll_event {
+                    events: registration.interest(),
+                    u64: fd as u64,
+                };
+                unsafe { libc::epoll_ctl(epoll, libc::EPOLL_CTL_MOD, fd, &mut rearm) };
+            }
+        }
+        self.waiters -= woken.len();
+        woken
+    }
+}
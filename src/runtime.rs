@@ -0,0 +1,1142 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::os::fd::RawFd;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::blocking::{Blocking, Job};
+use crate::builder::Builder;
+use crate::context::{ThreadContext, switch};
+use crate::join::JoinHandle;
+use crate::multi;
+use crate::pool::{DEFAULT_MAX_POOLED_STACKS, StackPool};
+use crate::reactor::{Direction, Reactor};
+use crate::scheduler::{Priority, Scheduler, Task};
+use crate::signal::install_overflow_handler;
+use crate::stack::Stack;
+use crate::timer::TimerWheel;
+#[cfg(feature = "io-uring")]
+use crate::uring::Uring;
+
+pub(crate) const DEFAULT_STACK_SIZE: usize = 1024 * 1024 * 2;
+const DEFAULT_MAX_THREADS: usize = 1024;
+
+// 下一个 runtime 的编号，不复用，所以丢弃的 runtime 的句柄也不会认错
+static NEXT_RUNTIME_ID: AtomicU64 = AtomicU64::new(1);
+
+// 等待者：(线程编号, generation)。反应器、spawn_blocking 和 io_uring 完成时用它唤醒协程，
+// generation 不同说明槽位已经换了任务
+pub(crate) type Waiter = (usize, u64);
+
+thread_local! {
+    // 每个 OS 线程有自己的当前 runtime，互不影响
+    static RUNTIME: Cell<*mut Inner> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum State {
+    Available, // 表示线程可用，并且可以根据需要分配任务
+    Running,   // 意味着线程正在运行
+    Ready,     // 意味着线程已准备好继续前进和恢复执行，已经调度过了等待恢复
+    Blocked,   // park 中，等待 unpark
+    Sleeping,  // 在时间轮中等待定时器到期
+    Finished,  // 任务已经结束，结果还没被 join 取走
+    Cancelled, // 任务被取消，没有结果
+}
+
+impl State {
+    // 合法的状态变化，其他的都是 runtime 的 bug
+    fn can_become(self, to: State) -> bool {
+        use State::*;
+        matches!(
+            (self, to),
+            (Available, Ready)
+                | (Ready, Running)
+                | (Running, Ready | Blocked | Sleeping | Finished | Cancelled)
+                | (Blocked | Sleeping, Ready)
+                | (Finished | Cancelled, Available)
+        )
+    }
+}
+
+// 阻塞的线程在等什么，死锁报告中使用
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum WaitReason {
+    Park,
+    Join(usize),
+    Readable(RawFd),
+    Writable(RawFd),
+    Blocking,
+    #[cfg(feature = "io-uring")]
+    Uring,
+}
+
+impl fmt::Display for WaitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WaitReason::Park => write!(f, "park()"),
+            WaitReason::Join(id) => write!(f, "join of coroutine {}", id),
+            WaitReason::Readable(fd) => write!(f, "fd {} to become readable", fd),
+            WaitReason::Writable(fd) => write!(f, "fd {} to become writable", fd),
+            WaitReason::Blocking => write!(f, "a spawn_blocking call"),
+            #[cfg(feature = "io-uring")]
+            WaitReason::Uring => write!(f, "an io_uring completion"),
+        }
+    }
+}
+
+// 等待 fd 的协程被取消、展开栈时撤销在反应器中的登记，否则 run 会一直等这个 fd
+struct FdWait {
+    fd: RawFd,
+    direction: Direction,
+    waiter: Waiter,
+}
+
+impl Drop for FdWait {
+    fn drop(&mut self) {
+        current_runtime()
+            .reactor
+            .cancel(self.fd, self.direction, self.waiter);
+    }
+}
+
+// 等待 io_uring 操作的协程展开栈时等内核放弃这个操作，之后操作引用的栈上内存才能释放
+#[cfg(feature = "io-uring")]
+struct UringOp {
+    token: u64,
+}
+
+#[cfg(feature = "io-uring")]
+impl Drop for UringOp {
+    fn drop(&mut self) {
+        current_runtime().uring.cancel(self.token);
+    }
+}
+
+// 取消协程时用来展开栈的 panic 负载
+pub(crate) struct Cancelled;
+
+// timeout 到期时用来展开栈的 panic 负载，带着到期的定时器编号
+pub(crate) struct TimedOut(pub(crate) u64);
+
+struct Thread {
+    id: usize,
+    name: Option<String>,
+    priority: u8,
+    deadline: Option<Instant>,
+    deadline_missed: bool, // 当前截止时间已经计过一次错过
+    missed_deadlines: usize,
+    stack: Option<Stack>, // base thread 直接使用 OS 线程的栈，其他线程结束后栈回到 StackPool
+    peak_stack: Option<usize>, // 结束时测到的栈最高水位，栈回到池子之后仍然可以查询
+    ctx: ThreadContext,
+    state: State,
+    task: Option<Box<dyn FnOnce() -> Box<dyn Any>>>,
+    result: Option<thread::Result<Box<dyn Any>>>, // 任务结束后的返回值或 panic，等待 JoinHandle 取走
+    detached: bool,                               // JoinHandle 已经被丢弃，结果不需要保存
+    waiter: Option<Waiter>,                       // 正在 join 这个线程的线程和它的 generation
+    caller: usize,                                // 非对称调用时的调用者线程ID
+    sleep: Option<(u64, Instant)>, // Sleeping 时睡眠定时器的编号和到期时间，提前醒来时从时间轮删除
+    generation: u64, // 每次 spawn 到这个槽位加一，旧的 Coroutine 句柄 unpark 时不会唤醒新任务
+    unparked: bool,  // unpark 先于 park 到达时留下的许可
+    cancel: bool,    // 已经请求取消，下一次恢复运行时展开栈
+    timeouts: Vec<(u64, Instant)>, // 生效中的 timeout 的定时器编号和到期时间，外层的在前（编号也更小）
+    elapsed: Option<u64>,          // 已经到期、还没有送达的 timeout，恢复运行或者阻塞时展开栈
+    waiting: WaitReason,           // 最近一次 park 的原因，Blocked 时有效
+    spawned_at: Option<&'static Location<'static>>, // base thread 没有
+}
+
+impl Thread {
+    fn new(id: usize) -> Self {
+        Thread {
+            id,
+            name: None,
+            priority: 0,
+            deadline: None,
+            deadline_missed: false,
+            missed_deadlines: 0,
+            stack: None,
+            peak_stack: None,
+            ctx: ThreadContext::default(),
+            state: State::Available,
+            task: None,
+            result: None,
+            detached: false,
+            waiter: None,
+            caller: 0,
+            sleep: None,
+            generation: 0,
+            unparked: false,
+            cancel: false,
+            timeouts: Vec::new(),
+            elapsed: None,
+            waiting: WaitReason::Park,
+            spawned_at: None,
+        }
+    }
+}
+
+/// spawn 失败的原因
+#[derive(Debug)]
+pub enum SpawnError {
+    /// 协程数量已经达到 `max_threads` 上限
+    LimitReached(usize),
+    /// 分配协程栈失败
+    Stack(io::Error),
+    /// 在 `MultiRuntime` 的协程中调用，需要使用 `MultiRuntime::spawnf`
+    MultiRuntime,
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::LimitReached(max) => {
+                write!(f, "no available thread, limit of {} reached", max)
+            }
+            SpawnError::Stack(e) => write!(f, "failed to allocate coroutine stack: {}", e),
+            SpawnError::MultiRuntime => write!(
+                f,
+                "cannot spawn onto a Runtime inside a MultiRuntime coroutine, use MultiRuntime::spawnf"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// `Runtime::run` 返回的统计信息，只统计这一次 run 期间的数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// 结束的协程数量，包括 panic 的
+    pub tasks: usize,
+    /// 上下文切换次数
+    pub switches: u64,
+    /// panic 的协程数量
+    pub panics: usize,
+}
+
+/// 协程运行时，`init` 之后成为当前 OS 线程的 runtime
+///
+/// 状态放在 Box 中，线程局部变量登记的是 Box 的地址，`Runtime` 本身可以随意移动
+pub struct Runtime {
+    inner: Box<Inner>,
+}
+
+pub(crate) struct Inner {
+    id: u64, // 每个 runtime 不同，句柄用它检查自己属于哪个 runtime
+    // Box 保证 Thread 地址不随 Vec 扩容变化，thread_ptr 一直有效
+    #[allow(clippy::vec_box)]
+    threads: Vec<Box<Thread>>,
+    current: usize,
+    scheduler: Box<dyn Scheduler>, // 除了正在运行的线程，所有 Ready 的线程都交给它
+    max_threads: usize,            // 不包括 base thread
+    pool: StackPool,
+    dead: Option<usize>, // 刚结束的线程，切换走之后才能回收它的栈
+    paint_stacks: bool,
+    stats: RunSummary,                // 从创建开始累计
+    timers: TimerWheel<(usize, u64)>, // (线程编号, 定时器编号)
+    reactor: Reactor,
+    blocking: Blocking,
+    #[cfg(feature = "io-uring")]
+    uring: Uring,
+    deadlock: Option<String>, // 在协程中发现死锁时交给 base thread 报告
+    sleeping: usize,          // Sleeping 状态的线程数
+    timeouts: usize,          // 所有线程中生效的 timeout 数量
+    next_timer: u64,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // 注册的 runtime 被释放后不能再通过线程局部变量访问
+        let r_ptr: *mut Inner = &mut *self.inner;
+        RUNTIME.with(|rt| {
+            if rt.get() == r_ptr {
+                rt.set(std::ptr::null_mut());
+            }
+        });
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Runtime {
+            inner: Box::new(Inner::new()),
+        }
+    }
+
+    /// 设置调度策略，默认是带老化的 `Priority`
+    ///
+    /// 必须在 spawn 之前设置，已经就绪的协程不会转移到新的调度器
+    pub fn with_scheduler<S: Scheduler + 'static>(mut self, scheduler: S) -> Self {
+        self.inner.scheduler = Box::new(scheduler);
+        self
+    }
+
+    /// 是否在分配栈时填充哨兵值来测量栈使用量，默认关闭
+    ///
+    /// 填充会让整个栈的页都被分配出来，每个协程占用完整的栈大小的内存，只在调试时打开
+    pub fn with_stack_usage(mut self, enabled: bool) -> Self {
+        self.inner.paint_stacks = enabled;
+        self
+    }
+
+    /// 设置最多可以同时存在的协程数量，超过后 spawn 返回 `SpawnError::LimitReached`
+    pub fn with_max_threads(mut self, max_threads: usize) -> Self {
+        self.inner.max_threads = max_threads;
+        self
+    }
+
+    /// 设置最多缓存多少个空闲栈，超过的栈在协程结束时直接释放，默认 64
+    pub fn with_max_pooled_stacks(mut self, max: usize) -> Self {
+        self.inner.pool.set_max(max);
+        self
+    }
+
+    /// 把 runtime 注册为当前 OS 线程的 runtime，丢弃 runtime 时取消注册
+    ///
+    /// 同时安装 SIGSEGV 处理函数，协程栈溢出时报告是哪个协程
+    pub fn init(&mut self) {
+        let r_ptr: *mut Inner = &mut *self.inner;
+        RUNTIME.with(|rt| rt.set(r_ptr));
+        install_overflow_handler();
+    }
+
+    /// 运行所有协程直到全部结束，然后把控制权还给调用者
+    ///
+    /// 没有 Ready 的协程但还有协程在睡眠或者等待 fd 时，OS 线程阻塞在 epoll 上，
+    /// 超时时间是最近的定时器。
+    /// 可以多次调用：之后再 spawn 的协程由下一次 run 执行。
+    /// 必须是当前 OS 线程上最后一次 `init` 的 runtime，否则 panic
+    pub fn run(&mut self) -> RunSummary {
+        let r_ptr: *mut Inner = &mut *self.inner;
+        // 协程通过线程局部变量找 runtime，注册的不是自己的话协程会在别的 runtime 上切换
+        assert!(
+            RUNTIME.with(Cell::get) == r_ptr,
+            "Runtime::run called on a runtime that is not registered on this OS thread, call init first"
+        );
+        self.inner.run()
+    }
+
+    /// 编号为 `id` 的协程栈的最高水位（字节）
+    ///
+    /// 协程运行中返回目前为止的峰值，结束后返回结束时的峰值；没有开启测量时返回 `None`
+    pub fn stack_usage(&self, id: usize) -> Option<usize> {
+        self.inner.stack_usage(id)
+    }
+
+    /// 编号为 `id` 的协程错过截止时间的次数，协程结束后仍然可以查询
+    pub fn missed_deadlines(&self, id: usize) -> usize {
+        self.inner.missed_deadlines(id)
+    }
+
+    /// 使用默认配置创建协程，需要自定义栈大小等参数时使用 `Builder`
+    #[track_caller]
+    pub fn spawnf<F, T>(f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        Builder::new().spawn(f)
+    }
+
+    /// 创建带截止时间的协程，配合 `scheduler::Edf` 使用
+    #[track_caller]
+    pub fn spawn_with_deadline<F, T>(deadline: Instant, f: F) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        Builder::new().deadline(deadline).spawn(f)
+    }
+}
+
+impl Inner {
+    fn new() -> Self {
+        let mut base_thread = Thread::new(0);
+        base_thread.state = State::Running;
+
+        // 其他线程在 spawn 时按需创建
+        Inner {
+            id: NEXT_RUNTIME_ID.fetch_add(1, Ordering::Relaxed),
+            threads: vec![Box::new(base_thread)],
+            current: 0,
+            scheduler: Box::new(Priority::new()),
+            max_threads: DEFAULT_MAX_THREADS,
+            pool: StackPool::new(DEFAULT_MAX_POOLED_STACKS),
+            dead: None,
+            paint_stacks: false,
+            stats: RunSummary::default(),
+            timers: TimerWheel::new(),
+            sleeping: 0,
+            timeouts: 0,
+            reactor: Reactor::new(),
+            blocking: Blocking::new(),
+            #[cfg(feature = "io-uring")]
+            uring: Uring::new(),
+            deadlock: None,
+            next_timer: 0,
+        }
+    }
+
+    fn run(&mut self) -> RunSummary {
+        assert_eq!(
+            self.current, 0,
+            "Runtime::run called from inside a coroutine"
+        );
+        let start = self.stats;
+
+        loop {
+            if self.t_yield() {
+                continue; // thread 1 | thread 2 执行一遍就返回 base_thread 执行 yield 回来
+            }
+            if !self.wait_events() {
+                if self.threads.iter().any(|t| t.state == State::Blocked) {
+                    self.deadlock();
+                }
+                break;
+            }
+        }
+
+        RunSummary {
+            tasks: self.stats.tasks - start.tasks,
+            switches: self.stats.switches - start.switches,
+            panics: self.stats.panics - start.panics,
+        }
+    }
+
+    // 栈结束时候，结果被取走或者不需要保存时槽位变回可用
+    fn t_return(&mut self, cancelled: bool) {
+        if self.current != 0 {
+            let id = self.current;
+            self.stats.tasks += 1;
+            self.check_deadline(id);
+            self.set_state(
+                id,
+                if cancelled {
+                    State::Cancelled
+                } else {
+                    State::Finished
+                },
+            );
+            if self.threads[id].detached {
+                self.set_state(id, State::Available); // 当前线程需要重新分配任务
+            }
+            self.dead = Some(id); // 还在这个栈上运行，由下一个线程回收
+            self.scheduler.on_exit(id);
+
+            // join 的线程下一个运行
+            if let Some((waiter, generation)) = self.threads[id].waiter.take() {
+                self.wake(waiter, generation, true);
+            }
+            self.t_block();
+        }
+    }
+
+    // 所有状态变化都经过这里，debug 构建中发现非法的变化直接报告
+    fn set_state(&mut self, id: usize, to: State) {
+        let from = self.threads[id].state;
+        debug_assert!(
+            from.can_become(to),
+            "illegal state transition for coroutine {}: {:?} -> {:?}",
+            id,
+            from,
+            to
+        );
+        self.threads[id].state = to;
+
+        if from == State::Sleeping {
+            self.sleeping -= 1;
+            // 被取消或者 timeout 到期提前醒来，睡眠定时器不再需要
+            if let Some((timer, deadline)) = self.threads[id].sleep.take() {
+                self.timers.remove(deadline, &(id, timer));
+            }
+        }
+        if to == State::Sleeping {
+            self.sleeping += 1;
+        }
+    }
+
+    // 每次切换回来（或者新线程开始运行）时调用，把上一个结束的线程的栈放回池子
+    fn reclaim(&mut self) {
+        if let Some(dead) = self.dead.take()
+            && let Some(stack) = self.threads[dead].stack.take()
+        {
+            self.threads[dead].peak_stack = stack.used();
+            self.pool.put(stack);
+        }
+    }
+
+    // 对称yield - 切换到任意ready线程
+    #[inline(never)]
+    pub(crate) fn t_yield(&mut self) -> bool {
+        // 当前线程也参与调度，选中自己或者没有 ready 的 thread 时继续运行
+        self.fire_events();
+        self.make_ready();
+        let Some(pos) = self.scheduler.pick_next() else {
+            return false;
+        };
+        if pos == self.current {
+            self.set_state(pos, State::Running);
+            return false;
+        }
+
+        self.switch_to(pos);
+        true
+    }
+
+    // 当前线程已经不能继续运行（阻塞、睡眠或者已经结束）：切换到下一个 Ready 线程，
+    // 没有的话等最近的定时器，唤醒的可能就是当前线程自己
+    fn t_block(&mut self) {
+        loop {
+            self.fire_events();
+            if let Some(pos) = self.scheduler.pick_next() {
+                if pos == self.current {
+                    self.set_state(pos, State::Running);
+                } else {
+                    self.switch_to(pos);
+                }
+                return;
+            }
+            if !self.wait_events() {
+                self.deadlock();
+            }
+        }
+    }
+
+    // 没有 Ready 的线程，也没有线程在睡眠或者等 fd，但还有线程阻塞着：永远不会再被唤醒了。
+    // 在 base thread 上 panic，协程中的 panic 会被它自己的 catch_unwind 截住
+    fn deadlock(&mut self) {
+        let report = self.deadlock_report();
+        if self.current == 0 {
+            panic!("{}", report);
+        }
+
+        // base thread 也阻塞着，唤醒它并让它报告
+        self.deadlock = Some(report);
+        self.set_state(0, State::Ready);
+        self.switch_to(0);
+    }
+
+    fn deadlock_report(&self) -> String {
+        use std::fmt::Write;
+
+        let mut report = String::from("deadlock: every rustcoro coroutine is blocked");
+        for t in self.threads.iter().filter(|t| t.state == State::Blocked) {
+            let _ = match t.id {
+                0 => write!(report, "\n  base thread"),
+                id => write!(report, "\n  coroutine {}", id),
+            };
+            if let Some(name) = &t.name {
+                let _ = write!(report, " ({})", name);
+            }
+            let _ = write!(report, " waiting on {}", t.waiting);
+            if let Some(location) = t.spawned_at {
+                let _ = write!(report, ", spawned at {}", location);
+            }
+        }
+        report
+    }
+
+    // 当前线程睡到 deadline
+    pub(crate) fn t_sleep_until(&mut self, deadline: Instant) {
+        let id = self.current;
+        self.next_timer += 1;
+        self.threads[id].sleep = Some((self.next_timer, deadline));
+        self.timers.insert(deadline, (id, self.next_timer));
+        self.block_current(State::Sleeping);
+    }
+
+    // 当前线程阻塞直到被 unpark，之前已经有 unpark 的话直接返回
+    pub(crate) fn t_park(&mut self, reason: WaitReason) {
+        if std::mem::take(&mut self.threads[self.current].unparked) {
+            return;
+        }
+        self.threads[self.current].waiting = reason;
+        self.block_current(State::Blocked);
+    }
+
+    // 唤醒 park 中的线程，线程没有 park 的话留下许可；generation 不同说明槽位已经换了任务
+    //
+    // unpark 和 I/O 完成唤醒的线程和定时器到期一样排到就绪队列后面，
+    // 否则互相唤醒的协程或者每次都立即完成的 I/O 会一直占着 OS 线程
+    pub(crate) fn unpark(&mut self, id: usize, generation: u64) {
+        self.wake(id, generation, false);
+    }
+
+    // joined：join 的目标结束了，交给调度器的 on_wake，可以插队
+    fn wake(&mut self, id: usize, generation: u64, joined: bool) {
+        let Some(thread) = self.threads.get_mut(id) else {
+            return;
+        };
+        if thread.generation != generation {
+            return;
+        }
+        if thread.state == State::Blocked {
+            self.set_state(id, State::Ready);
+            let task = self.task(id);
+            if joined {
+                self.scheduler.on_wake(task);
+            } else {
+                self.scheduler.on_ready(task);
+            }
+        } else {
+            thread.unparked = true;
+        }
+    }
+
+    // 请求取消：还没开始的任务不再运行，已经开始的在下一次恢复运行时展开栈
+    pub(crate) fn cancel(&mut self, id: usize) -> bool {
+        match self.threads[id].state {
+            State::Ready => {}
+            State::Blocked | State::Sleeping => {
+                self.set_state(id, State::Ready);
+                let task = self.task(id);
+                self.scheduler.on_ready(task);
+            }
+            // 正在运行的就是调用者自己，或者任务已经结束
+            _ => return false,
+        }
+        self.threads[id].cancel = true;
+        true
+    }
+
+    // 当前线程进入 Blocked 或 Sleeping，切换到别的线程直到被唤醒
+    fn block_current(&mut self, state: State) {
+        let id = self.current;
+        self.deliver_timeout();
+        self.check_deadline(id);
+        self.set_state(id, state);
+        self.scheduler.on_block(id);
+        self.t_block();
+        // t_block 选中自己时不经过 switch_to
+        self.deliver_timeout();
+    }
+
+    // 当前线程开始一个到 deadline 为止的 timeout，返回它的定时器编号
+    pub(crate) fn t_start_timeout(&mut self, deadline: Instant) -> u64 {
+        let id = self.current;
+        self.next_timer += 1;
+        self.timers.insert(deadline, (id, self.next_timer));
+        self.threads[id].timeouts.push((self.next_timer, deadline));
+        self.timeouts += 1;
+        self.next_timer
+    }
+
+    // timeout 的作用范围结束（正常返回或者展开栈），还没到期的话从时间轮删除，
+    // 到期了但还没有送达的话不再送达
+    pub(crate) fn t_end_timeout(&mut self, timer: u64) {
+        let id = self.current;
+        let thread = &mut self.threads[id];
+        let index = thread
+            .timeouts
+            .iter()
+            .position(|&(t, _)| t == timer)
+            .unwrap();
+        let (_, deadline) = thread.timeouts.remove(index);
+        if thread.elapsed == Some(timer) {
+            thread.elapsed = None;
+        }
+        self.timers.remove(deadline, &(id, timer));
+        self.timeouts -= 1;
+    }
+
+    // timeout 到期：记下来，阻塞中的线程唤醒后展开栈，离开等待它的地方
+    fn fire_timeout(&mut self, id: usize, timer: u64) {
+        let thread = &mut self.threads[id];
+        // 外层的 timeout 到期时内层的也要一起退出
+        if thread.elapsed.is_none_or(|e| timer < e) {
+            thread.elapsed = Some(timer);
+        }
+        if matches!(thread.state, State::Blocked | State::Sleeping) {
+            self.set_state(id, State::Ready);
+            let task = self.task(id);
+            self.scheduler.on_ready(task);
+        }
+    }
+
+    fn deliver_timeout(&mut self) {
+        if let Some(timer) = self.threads[self.current].elapsed.take() {
+            panic::resume_unwind(Box::new(TimedOut(timer)));
+        }
+    }
+
+    // 把定时器到期的线程放回调度器
+    fn fire_timers(&mut self) {
+        if self.timers.is_empty() {
+            return;
+        }
+        for (id, timer) in self.timers.expire(Instant::now()) {
+            if self.threads[id].sleep.is_some_and(|(t, _)| t == timer) {
+                self.threads[id].sleep = None;
+                self.set_state(id, State::Ready);
+                let task = self.task(id);
+                self.scheduler.on_ready(task);
+            } else if self.threads[id].timeouts.iter().any(|&(t, _)| t == timer) {
+                self.fire_timeout(id, timer);
+            }
+        }
+    }
+
+    // 不阻塞地检查到期的定时器和就绪的 fd
+    fn fire_events(&mut self) {
+        self.fire_timers();
+        if self.io_waiters() > 0 {
+            self.poll_io(Some(Duration::ZERO));
+        }
+    }
+
+    // 等 fd 就绪、spawn_blocking 或者 io_uring 操作完成的线程数
+    fn io_waiters(&self) -> usize {
+        let waiters = self.reactor.waiters() + self.blocking.waiters();
+        #[cfg(feature = "io-uring")]
+        let waiters = waiters + self.uring.waiters();
+        waiters
+    }
+
+    fn poll_io(&mut self, timeout: Option<Duration>) {
+        for (id, generation) in self.reactor.poll(timeout) {
+            self.unpark(id, generation);
+        }
+        for (id, generation) in self.blocking.complete() {
+            self.unpark(id, generation);
+        }
+        #[cfg(feature = "io-uring")]
+        for (id, generation) in self.uring.complete() {
+            self.unpark(id, generation);
+        }
+    }
+
+    // 没有 Ready 的线程时阻塞 OS 线程，直到最近的定时器到期或者有 fd 就绪；
+    // 没有线程在睡眠、没有生效的 timeout 也没有线程在等 fd 时返回 false
+    fn wait_events(&mut self) -> bool {
+        let io = self.io_waiters() > 0;
+        let timed = self.sleeping > 0 || self.timeouts > 0;
+        if !timed && !io {
+            return false;
+        }
+
+        let timeout = match self.timers.next_deadline() {
+            Some(deadline) if timed => Some(deadline.saturating_duration_since(Instant::now())),
+            _ => None,
+        };
+        if io {
+            self.poll_io(timeout);
+        } else if let Some(timeout) = timeout {
+            thread::sleep(timeout);
+        }
+        self.fire_timers();
+        true
+    }
+
+    // 当前线程等待 fd 可读或可写
+    pub(crate) fn t_wait_fd(&mut self, fd: RawFd, direction: Direction) -> io::Result<()> {
+        let waiter = self.current_coroutine();
+        self.reactor.register(fd, direction, waiter)?;
+        let _registration = FdWait {
+            fd,
+            direction,
+            waiter,
+        };
+
+        let reason = match direction {
+            Direction::Read => WaitReason::Readable(fd),
+            Direction::Write => WaitReason::Writable(fd),
+        };
+        while self.reactor.is_waiting(fd, direction, waiter) {
+            self.t_park(reason);
+        }
+        Ok(())
+    }
+
+    // 把任务交给 spawn_blocking 线程池，完成时唤醒当前线程
+    pub(crate) fn t_submit_blocking(&mut self, job: Job) -> io::Result<u64> {
+        let waiter = self.current_coroutine();
+        self.blocking.submit(&mut self.reactor, job, waiter)
+    }
+
+    pub(crate) fn blocking_running(&self, token: u64) -> bool {
+        self.blocking.is_running(token)
+    }
+
+    pub(crate) fn forget_blocking(&mut self, token: u64) {
+        self.blocking.forget(token);
+    }
+
+    // 当前线程提交一个 io_uring 操作并等待它完成，返回 CQE 的结果
+    //
+    // 调用者保证操作引用的内存在返回之前有效；协程被取消时 UringOp 会等操作真正结束
+    #[cfg(feature = "io-uring")]
+    pub(crate) unsafe fn t_submit(&mut self, entry: io_uring::squeue::Entry) -> io::Result<i32> {
+        let waiter = self.current_coroutine();
+        let token = unsafe { self.uring.submit(&mut self.reactor, entry, waiter)? };
+        let _op = UringOp { token };
+
+        loop {
+            if let Some(result) = self.uring.take_result(token) {
+                if result < 0 {
+                    return Err(io::Error::from_raw_os_error(-result));
+                }
+                return Ok(result);
+            }
+            self.t_park(WaitReason::Uring);
+        }
+    }
+
+    // 非对称yield - 只能切换回调用者
+    fn t_yield_to_caller(&mut self) -> bool {
+        let caller = self.threads[self.current].caller;
+        if caller == self.current || self.threads[caller].state != State::Ready {
+            return false;
+        }
+
+        self.scheduler.on_block(caller);
+        self.make_ready();
+        self.switch_to(caller);
+        true
+    }
+
+    // 调用另一个协程，被调用者 yield_to_caller 时回到当前协程
+    fn t_call(&mut self, callee: usize) -> bool {
+        if callee >= self.threads.len() || self.threads[callee].state != State::Ready {
+            return false;
+        }
+
+        self.threads[callee].caller = self.current;
+        self.scheduler.on_block(callee);
+        self.make_ready();
+        self.switch_to(callee);
+        true
+    }
+
+    fn task(&self, id: usize) -> Task {
+        Task {
+            id,
+            priority: self.threads[id].priority,
+            deadline: self.threads[id].deadline,
+        }
+    }
+
+    // 当前线程让出 CPU：running -> ready，已经结束的线程不再调度
+    fn make_ready(&mut self) {
+        self.check_deadline(self.current);
+        if self.threads[self.current].state == State::Running {
+            self.set_state(self.current, State::Ready);
+            let task = self.task(self.current);
+            self.scheduler.on_ready(task);
+        }
+    }
+
+    // pos 必须已经不在就绪队列中，当前线程已经放回调度器（或者已经结束）
+    fn switch_to(&mut self, pos: usize) {
+        self.set_state(pos, State::Running); // 更新当前线程为 running 状态
+        let old_pos = self.current; // 切换索引
+        self.current = pos;
+        self.stats.switches += 1;
+
+        let old: *mut ThreadContext = &mut self.threads[old_pos].ctx;
+        let new: *const ThreadContext = &self.threads[pos].ctx;
+
+        unsafe {
+            switch(old, new);
+        }
+        self.reclaim();
+
+        // 等待期间被取消：展开栈执行 Drop，由 call 中的 catch_unwind 截住
+        if std::mem::take(&mut self.threads[self.current].cancel) {
+            panic::resume_unwind(Box::new(Cancelled));
+        }
+        self.deliver_timeout();
+        if let Some(report) = self.deadlock.take() {
+            panic!("{}", report);
+        }
+    }
+
+    // 在信号处理函数中调用，返回 guard page 包含 addr 的线程编号、名字和栈大小
+    pub(crate) fn overflowed_thread(&self, addr: usize) -> Option<(usize, Option<&str>, usize)> {
+        self.threads.iter().find_map(|t| {
+            let stack = t.stack.as_ref()?;
+            stack
+                .guard_contains(addr)
+                .then(|| (t.id, t.name.as_deref(), stack.size()))
+        })
+    }
+
+    pub(crate) fn stack_usage(&self, id: usize) -> Option<usize> {
+        let thread = self.threads.get(id)?;
+        match &thread.stack {
+            Some(stack) => stack.used(),
+            None => thread.peak_stack,
+        }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn is_finished(&self, id: usize) -> bool {
+        self.threads
+            .get(id)
+            .is_some_and(|t| matches!(t.state, State::Finished | State::Cancelled))
+    }
+
+    // join 等待的线程结束时，等待者会被唤醒并放到就绪队列的最前面
+    pub(crate) fn set_waiter(&mut self, id: usize) {
+        self.threads[id].waiter = Some(self.current_coroutine());
+    }
+
+    // 取走结果之后槽位可以复用
+    pub(crate) fn take_result(&mut self, id: usize) -> Option<thread::Result<Box<dyn Any>>> {
+        if !self.is_finished(id) {
+            return None;
+        }
+        self.set_state(id, State::Available);
+        self.threads[id].result.take()
+    }
+
+    // JoinHandle 被丢弃：已经结束的直接丢掉结果，还没结束的标记为 detached
+    pub(crate) fn detach(&mut self, id: usize) {
+        if self.is_finished(id) {
+            self.set_state(id, State::Available);
+            self.threads[id].result = None;
+        } else if let Some(thread) = self.threads.get_mut(id) {
+            thread.detached = true;
+            thread.waiter = None; // 比如 join 超时，不再有人等它结束
+        }
+    }
+
+    pub(crate) fn current_coroutine(&self) -> Waiter {
+        (self.current, self.threads[self.current].generation)
+    }
+
+    // 修改优先级，已经在调度器中的线程按新的优先级重新入队
+    pub(crate) fn set_priority(&mut self, id: usize, priority: u8) {
+        self.threads[id].priority = priority;
+        self.requeue(id);
+    }
+
+    // 设置新的截止时间，错过检查重新开始
+    pub(crate) fn set_deadline(&mut self, id: usize, deadline: Option<Instant>) {
+        let thread = &mut self.threads[id];
+        thread.deadline = deadline;
+        thread.deadline_missed = false;
+        self.requeue(id);
+    }
+
+    fn requeue(&mut self, id: usize) {
+        if self.threads[id].state == State::Ready {
+            self.scheduler.on_block(id);
+            let task = self.task(id);
+            self.scheduler.on_ready(task);
+        }
+    }
+
+    // 线程离开 CPU 时检查：过了截止时间还没结束就算错过一次，每个截止时间只算一次
+    fn check_deadline(&mut self, id: usize) {
+        let thread = &mut self.threads[id];
+        if let Some(deadline) = thread.deadline
+            && !thread.deadline_missed
+            && Instant::now() > deadline
+        {
+            thread.deadline_missed = true;
+            thread.missed_deadlines += 1;
+        }
+    }
+
+    pub(crate) fn missed_deadlines(&self, id: usize) -> usize {
+        self.threads.get(id).map_or(0, |t| t.missed_deadlines)
+    }
+
+    // 结果还没被 join 取走的槽位（Finished）不能复用，没有空闲槽位时在上限内新建一个
+    // 栈从 StackPool 中取，池子里没有合适大小的栈时才新分配
+    fn available_thread(&mut self, stack_size: usize) -> Result<&mut Thread, SpawnError> {
+        let pos = match self
+            .threads
+            .iter()
+            .position(|t| t.state == State::Available)
+        {
+            Some(pos) => pos,
+            None if self.threads.len() <= self.max_threads => {
+                let id = self.threads.len();
+                self.threads.push(Box::new(Thread::new(id)));
+                id
+            }
+            None => return Err(SpawnError::LimitReached(self.max_threads)),
+        };
+
+        let mut stack = self.pool.get(stack_size).map_err(SpawnError::Stack)?;
+        if self.paint_stacks {
+            stack.paint();
+        }
+        let thread = &mut self.threads[pos];
+        thread.stack = Some(stack);
+        thread.peak_stack = None;
+        Ok(thread)
+    }
+
+    #[track_caller]
+    pub(crate) fn spawn_with<F, T>(
+        &mut self,
+        builder: Builder,
+        f: F,
+    ) -> Result<JoinHandle<T>, SpawnError>
+    where
+        F: FnOnce() -> T + 'static,
+        T: 'static,
+    {
+        let available_thread = self.available_thread(builder.stack_size)?;
+        available_thread.task = Some(Box::new(move || Box::new(f()) as Box<dyn Any>));
+        available_thread.name = builder.name;
+        available_thread.priority = builder.priority;
+        available_thread.deadline = builder.deadline;
+        available_thread.deadline_missed = false;
+        available_thread.missed_deadlines = 0;
+        available_thread.detached = false;
+        available_thread.waiter = None;
+        available_thread.caller = 0;
+        available_thread.generation += 1;
+        available_thread.unparked = false;
+        available_thread.cancel = false;
+        available_thread.timeouts.clear();
+        available_thread.elapsed = None;
+        available_thread.spawned_at = Some(Location::caller());
+
+        unsafe {
+            let s_ptr = available_thread.stack.as_ref().unwrap().top();
+            let s_aligned = (s_ptr as usize & !15) as *mut u8;
+
+            available_thread.ctx.thread_ptr = available_thread as *const Thread as u64; // set thread pointer address
+
+            // 新栈布局：从高到低
+            // [s_aligned]      : 栈顶（未使用）
+            // s_aligned-8      : 占位，call 不会返回，保证进入 call 时 rsp 满足 16 字节对齐约定
+            // s_aligned-16     : call地址 ← RSP初始位置
+            std::ptr::write(s_aligned.offset(-8) as *mut u64, 0);
+            std::ptr::write(s_aligned.offset(-16) as *mut u64, call as *const () as u64);
+            available_thread.ctx.rsp = s_aligned.offset(-16) as u64;
+        }
+
+        let id = available_thread.id;
+        self.set_state(id, State::Ready);
+        let task = self.task(id);
+        self.scheduler.on_ready(task);
+        Ok(JoinHandle::new(self.id, id))
+    }
+}
+
+// 不内联：编译器不能把线程局部变量的地址缓存在跨越 switch 的调用者里
+#[inline(never)]
+pub(crate) fn current_runtime() -> &'static mut Inner {
+    let rt_ptr = RUNTIME.with(Cell::get);
+    if rt_ptr.is_null() {
+        no_runtime();
+    }
+    unsafe { &mut *rt_ptr }
+}
+
+#[cold]
+fn no_runtime() -> ! {
+    if multi::in_coroutine() {
+        panic!(
+            "rustcoro runtime APIs (spawn, JoinHandle, park, time, net, ...) are not available \
+             inside MultiRuntime coroutines, only MultiRuntime::spawnf and yield_now are"
+        );
+    }
+    panic!("no rustcoro runtime on this OS thread, create one and call Runtime::init first");
+}
+
+// 信号处理函数和 JoinHandle 的 Drop 中使用，不能 panic
+#[inline(never)]
+pub(crate) fn try_current_runtime() -> Option<&'static mut Inner> {
+    unsafe { RUNTIME.with(Cell::get).as_mut() }
+}
+
+// 句柄所属的 runtime，必须是当前注册的那个：句柄中的线程编号在别的 runtime 中没有意义
+pub(crate) fn runtime_of(id: u64) -> &'static mut Inner {
+    let rt = current_runtime();
+    assert!(
+        rt.id == id,
+        "handle used on a different rustcoro runtime than the one that created it"
+    );
+    rt
+}
+
+// switch 通过 ret 跳到这里，rdi 中是 thread_ptr
+extern "C" fn call(thread: u64) -> ! {
+    current_runtime().reclaim(); // 新线程第一次运行，没有经过 switch_to 的返回路径
+    let thread = unsafe { &mut *(thread as *mut Thread) };
+
+    // panic 不能跨过 switch 的汇编栈帧展开，在这里截住，交给 join 的调用者
+    // 还没开始就被取消的任务不再运行
+    let result = match thread.task.take() {
+        Some(f) if !std::mem::take(&mut thread.cancel) => panic::catch_unwind(AssertUnwindSafe(f)),
+        _ => Err(Box::new(Cancelled) as Box<dyn Any + Send>),
+    };
+    let cancelled = matches!(&result, Err(payload) if payload.is::<Cancelled>());
+    if result.is_err() && !cancelled {
+        current_runtime().stats.panics += 1;
+    }
+    if !thread.detached {
+        thread.result = Some(result);
+    }
+
+    guard(cancelled);
+}
+
+fn guard(cancelled: bool) -> ! {
+    current_runtime().t_return(cancelled);
+    unreachable!("finished thread resumed");
+}
+
+/// 在当前 runtime 上创建一个协程，返回可以 join 的句柄
+///
+/// 协程数量达到上限时 panic，需要处理错误时使用 `Runtime::spawnf`
+#[track_caller]
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + 'static,
+    T: 'static,
+{
+    Runtime::spawnf(f).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// 让出执行权，切换到下一个 ready 的协程
+///
+/// 在 `MultiRuntime` 的协程中调用时切回 worker 的调度循环，之后可能在另一个 OS 线程上恢复
+pub fn yield_now() {
+    if multi::in_coroutine() {
+        multi::yield_now();
+        return;
+    }
+    current_runtime().t_yield();
+}
+
+/// 修改当前协程的优先级，数值越大越优先
+pub fn set_priority(priority: u8) {
+    let rt = current_runtime();
+    rt.set_priority(rt.current, priority);
+}
+
+/// 修改当前协程的截止时间，周期性的任务每一轮开始时设置下一个截止时间
+pub fn set_deadline(deadline: Instant) {
+    let rt = current_runtime();
+    rt.set_deadline(rt.current, Some(deadline));
+}
+
+/// 切换回通过 `call_thread` 调用当前协程的协程
+pub fn yield_to_caller() -> bool {
+    current_runtime().t_yield_to_caller()
+}
+
+/// 调用编号为 `callee` 的协程，直到它 `yield_to_caller`
+pub fn call_thread(callee: usize) -> bool {
+    current_runtime().t_call(callee)
+}
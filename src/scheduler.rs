@@ -0,0 +1,490 @@
+//! 调度策略：`Runtime` 只负责切换上下文，下一个运行哪个协程由 `Scheduler` 决定
+//!
+//! ```ignore
+//! let mut runtime = Runtime::new().with_scheduler(Priority::new());
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
+use std::time::Instant;
+
+use crate::queue::ReadyQueue;
+
+// 默认每等待 64 次调度提高一级有效优先级
+const DEFAULT_AGING: u64 = 64;
+
+// 连续插队最多这么多次，之后 join 的等待者也排到队尾，排队的协程不会一直被插队
+const MAX_WAKE_STREAK: u32 = 3;
+
+/// 调度器看到的协程信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Task {
+    pub id: usize,
+    pub priority: u8,
+    pub deadline: Option<Instant>,
+}
+
+/// 调度策略
+///
+/// runtime 保证：正在运行的协程不在就绪集合中；同一个协程不会在没有离开就绪集合的情况下
+/// 被 `on_ready` 两次，除非是 `on_wake`
+pub trait Scheduler {
+    /// 协程变成 Ready：新创建的，或者 yield 让出执行权的
+    fn on_ready(&mut self, task: Task);
+
+    /// 协程不再 Ready 但还没有结束：被阻塞，或者被直接切换过去运行。还在就绪集合里的话要移除
+    fn on_block(&mut self, id: usize);
+
+    /// 协程结束，之后这个编号可能被新的协程复用
+    fn on_exit(&mut self, id: usize);
+
+    /// 选出下一个运行的协程并把它移出就绪集合，没有 Ready 的协程时返回 `None`
+    fn pick_next(&mut self) -> Option<usize>;
+
+    /// 协程 join 的目标结束了，默认和 `on_ready` 相同；`unpark`、I/O 完成等其他唤醒都走 `on_ready`
+    fn on_wake(&mut self, task: Task) {
+        self.on_ready(task);
+    }
+}
+
+/// 按线程编号轮转：从上一次运行的编号往后找下一个 Ready 的协程
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    ready: BTreeSet<usize>,
+    last: usize,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn on_ready(&mut self, task: Task) {
+        self.ready.insert(task.id);
+    }
+
+    fn on_block(&mut self, id: usize) {
+        self.ready.remove(&id);
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.ready.remove(&id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        let id = self
+            .ready
+            .range(self.last + 1..)
+            .next()
+            .or_else(|| self.ready.first())
+            .copied()?;
+        self.ready.remove(&id);
+        self.last = id;
+        Some(id)
+    }
+}
+
+/// 先就绪先运行，不看优先级，join 的目标结束后等待者插队到最前面
+pub struct Fifo {
+    queue: ReadyQueue,
+    wake_streak: u32, // 上一次 on_ready 之后连续插队的次数
+}
+
+impl Default for Fifo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Fifo {
+            queue: ReadyQueue::new(),
+            wake_streak: 0,
+        }
+    }
+}
+
+impl Scheduler for Fifo {
+    fn on_ready(&mut self, task: Task) {
+        self.wake_streak = 0;
+        self.queue.push(task.id);
+    }
+
+    fn on_block(&mut self, id: usize) {
+        self.queue.remove(id);
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.queue.remove(id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.queue.pop()
+    }
+
+    fn on_wake(&mut self, task: Task) {
+        if self.wake_streak < MAX_WAKE_STREAK {
+            self.queue.push_next(task.id);
+        } else {
+            self.queue.push(task.id);
+        }
+        self.wake_streak += 1;
+    }
+}
+
+/// 运行优先级最高的 Ready 协程，同一优先级内先就绪先运行（默认策略）
+///
+/// 等待中的协程会老化：每等待 `aging` 次调度，有效优先级提高一级，低优先级的协程不会被饿死。
+/// 重新就绪时有效优先级回到它自己的优先级
+pub struct Priority {
+    levels: Vec<VecDeque<(usize, u64, u64)>>, // 下标就是优先级，(线程编号, ticket, 入队时的 tick)
+    occupied: [u64; 4],                       // 非空的优先级，pick_next 只看这些级
+    tickets: Vec<u64>,                        // 每个线程当前有效的 ticket，0 表示不在队列中
+    next_ticket: u64,
+    tick: u64, // pick_next 的调用次数
+    aging: u64,
+    wake_streak: u32, // 上一次 on_ready 之后连续插队的次数
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Priority {
+    pub fn new() -> Self {
+        Priority {
+            levels: (0..=u8::MAX).map(|_| VecDeque::new()).collect(),
+            occupied: [0; 4],
+            tickets: Vec::new(),
+            next_ticket: 1,
+            tick: 0,
+            aging: DEFAULT_AGING,
+            wake_streak: 0,
+        }
+    }
+
+    /// 等待多少次调度提高一级有效优先级，默认 64，0 表示不老化（严格按优先级）
+    pub fn with_aging(mut self, picks: u64) -> Self {
+        self.aging = picks;
+        self
+    }
+
+    fn ticket(&mut self, id: usize) -> u64 {
+        if id >= self.tickets.len() {
+            self.tickets.resize(id + 1, 0);
+        }
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.tickets[id] = ticket;
+        ticket
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some(ticket) = self.tickets.get_mut(id) {
+            *ticket = 0;
+        }
+    }
+
+    // 丢掉队首已经被移除或者重新入队的记录，返回有效的队首
+    fn front(&mut self, level: usize) -> Option<(usize, u64, u64)> {
+        let queue = &mut self.levels[level];
+        while let Some(&(id, ticket, since)) = queue.front() {
+            if self.tickets[id] == ticket {
+                return Some((id, ticket, since));
+            }
+            queue.pop_front();
+        }
+        self.occupied[level / 64] &= !(1 << (level % 64));
+        None
+    }
+
+    fn push(&mut self, task: Task, front: bool) {
+        let ticket = self.ticket(task.id);
+        let level = task.priority as usize;
+        if front {
+            // 插队的记录继承原来队首的等待时间，否则排在后面的协程永远不会老化
+            let since = self.front(level).map_or(self.tick, |(.., since)| since);
+            self.levels[level].push_front((task.id, ticket, since));
+        } else {
+            self.levels[level].push_back((task.id, ticket, self.tick));
+        }
+        self.occupied[level / 64] |= 1 << (level % 64);
+    }
+}
+
+impl Scheduler for Priority {
+    fn on_ready(&mut self, task: Task) {
+        self.wake_streak = 0;
+        self.push(task, false);
+    }
+
+    fn on_block(&mut self, id: usize) {
+        self.remove(id);
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.remove(id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        self.tick += 1;
+
+        // 每一级的队首是这一级等待最久的，只需要从高到低比较非空各级队首的有效优先级
+        let mut best: Option<(usize, u64, u64)> = None; // (优先级, 有效优先级, ticket)
+        'levels: for word in (0..self.occupied.len()).rev() {
+            let mut bits = self.occupied[word];
+            while bits != 0 {
+                let bit = 63 - bits.leading_zeros() as usize;
+                bits &= !(1 << bit);
+                let level = word * 64 + bit;
+                let Some((_, ticket, since)) = self.front(level) else {
+                    continue;
+                };
+                if self.aging == 0 {
+                    best = Some((level, 0, 0));
+                    break 'levels;
+                }
+
+                let effective =
+                    (level as u64 + (self.tick - since) / self.aging).min(u8::MAX as u64);
+                if best.is_none_or(|(_, e, t)| effective > e || (effective == e && ticket < t)) {
+                    best = Some((level, effective, ticket));
+                }
+            }
+        }
+
+        let (level, ..) = best?;
+        let (id, ..) = self.levels[level].pop_front()?;
+        self.tickets[id] = 0;
+        if self.levels[level].is_empty() {
+            self.occupied[level / 64] &= !(1 << (level % 64));
+        }
+        Some(id)
+    }
+
+    fn on_wake(&mut self, task: Task) {
+        let front = self.wake_streak < MAX_WAKE_STREAK;
+        self.wake_streak += 1;
+        self.push(task, front);
+    }
+}
+
+// 排序键：(没有截止时间, 截止时间, ticket)，没有截止时间的排在最后，同样的截止时间先就绪先运行
+type EdfKey = (bool, Option<Instant>, u64);
+
+/// 最早截止时间优先：运行截止时间最近的 Ready 协程，没有截止时间的协程排在最后，先就绪先运行
+#[derive(Default)]
+pub struct Edf {
+    heap: BinaryHeap<Reverse<(EdfKey, usize)>>,
+    tickets: Vec<u64>, // 每个线程当前有效的 ticket，0 表示不在队列中
+    next_ticket: u64,
+}
+
+impl Edf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some(ticket) = self.tickets.get_mut(id) {
+            *ticket = 0;
+        }
+    }
+}
+
+impl Scheduler for Edf {
+    fn on_ready(&mut self, task: Task) {
+        if task.id >= self.tickets.len() {
+            self.tickets.resize(task.id + 1, 0);
+        }
+        self.next_ticket += 1;
+        self.tickets[task.id] = self.next_ticket;
+        let key = (task.deadline.is_none(), task.deadline, self.next_ticket);
+        self.heap.push(Reverse((key, task.id)));
+    }
+
+    fn on_block(&mut self, id: usize) {
+        self.remove(id);
+    }
+
+    fn on_exit(&mut self, id: usize) {
+        self.remove(id);
+    }
+
+    fn pick_next(&mut self) -> Option<usize> {
+        while let Some(Reverse(((_, _, ticket), id))) = self.heap.pop() {
+            if self.tickets[id] == ticket {
+                self.tickets[id] = 0;
+                return Some(id);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn task(id: usize, priority: u8) -> Task {
+        Task {
+            id,
+            priority,
+            deadline: None,
+        }
+    }
+
+    fn drain(scheduler: &mut impl Scheduler) -> Vec<usize> {
+        std::iter::from_fn(|| scheduler.pick_next()).collect()
+    }
+
+    #[test]
+    fn round_robin_rotates_by_id() {
+        let mut rr = RoundRobin::new();
+        for id in [3, 1, 2] {
+            rr.on_ready(task(id, 0));
+        }
+        assert_eq!(rr.pick_next(), Some(1));
+        rr.on_ready(task(1, 0));
+        assert_eq!(rr.pick_next(), Some(2));
+        rr.on_block(3);
+        assert_eq!(rr.pick_next(), Some(1)); // 绕回开头
+        assert_eq!(rr.pick_next(), None);
+    }
+
+    #[test]
+    fn fifo_runs_in_ready_order() {
+        let mut fifo = Fifo::new();
+        for id in [3, 1, 2] {
+            fifo.on_ready(task(id, 0));
+        }
+        fifo.on_exit(1);
+        assert_eq!(drain(&mut fifo), [3, 2]);
+    }
+
+    #[test]
+    fn fifo_wake_jumps_queue_up_to_cap() {
+        let mut fifo = Fifo::new();
+        fifo.on_ready(task(1, 0));
+        let woken: Vec<usize> = (2..2 + MAX_WAKE_STREAK as usize).collect();
+        for &id in &woken {
+            fifo.on_wake(task(id, 0));
+        }
+        // 超过上限的唤醒排到队尾
+        fifo.on_wake(task(100, 0));
+
+        // 最后插队的在 next 槽，之前被挤出来的依次排在 1 后面
+        let mut expected = vec![*woken.last().unwrap(), 1];
+        expected.extend(&woken[..woken.len() - 1]);
+        expected.push(100);
+        assert_eq!(drain(&mut fifo), expected);
+
+        // on_ready 之后重新允许插队
+        fifo.on_ready(task(1, 0));
+        fifo.on_wake(task(2, 0));
+        assert_eq!(drain(&mut fifo), [2, 1]);
+    }
+
+    #[test]
+    fn priority_picks_highest_then_fifo() {
+        let mut p = Priority::new().with_aging(0);
+        p.on_ready(task(1, 1));
+        p.on_ready(task(2, 200));
+        p.on_ready(task(3, 1));
+        p.on_ready(task(4, 255));
+        p.on_ready(task(5, 0));
+        p.on_block(3);
+        assert_eq!(drain(&mut p), [4, 2, 1, 5]);
+
+        // 重新入队只保留最后一次
+        p.on_ready(task(1, 1));
+        p.on_ready(task(2, 1));
+        p.on_ready(task(1, 1));
+        assert_eq!(drain(&mut p), [2, 1]);
+    }
+
+    #[test]
+    fn priority_aging_promotes_waiting_tasks() {
+        let mut p = Priority::new().with_aging(2);
+        p.on_ready(task(1, 0));
+        // 一个高一级的协程一直 yield，等待 2 次调度后 1 的有效优先级追上它
+        let mut picks = Vec::new();
+        p.on_ready(task(2, 1));
+        for _ in 0..4 {
+            let id = p.pick_next().unwrap();
+            picks.push(id);
+            if id == 2 {
+                p.on_ready(task(2, 1));
+            }
+        }
+        assert_eq!(picks, [2, 1, 2, 2]);
+    }
+
+    #[test]
+    fn priority_wake_jumps_within_level_up_to_cap() {
+        let mut p = Priority::new().with_aging(0);
+        p.on_ready(task(1, 5));
+        p.on_wake(task(2, 5));
+        p.on_wake(task(3, 9));
+        assert_eq!(drain(&mut p), [3, 2, 1]);
+
+        // 两个协程互相唤醒也不能一直排在 1 前面
+        p.on_ready(task(1, 5));
+        let mut picks = Vec::new();
+        let mut other = 2;
+        for _ in 0..MAX_WAKE_STREAK + 2 {
+            p.on_wake(task(other, 5));
+            let id = p.pick_next().unwrap();
+            picks.push(id);
+            if id == 1 {
+                break;
+            }
+            other = if id == 2 { 3 } else { 2 };
+        }
+        assert_eq!(picks.last(), Some(&1));
+        assert!(picks.len() <= MAX_WAKE_STREAK as usize + 1);
+    }
+
+    #[test]
+    fn priority_wake_keeps_level_aging() {
+        let mut p = Priority::new().with_aging(2);
+        p.on_ready(task(1, 0));
+        p.on_ready(task(9, 3));
+        for _ in 0..5 {
+            assert_eq!(p.pick_next(), Some(9));
+            p.on_ready(task(9, 3));
+        }
+        // 插队的 2 继承了 1 的等待时间，和 1 一样在第 7 次调度时赢过 9；
+        // 如果从 0 开始老化，2 和排在它后面的 1 都要再等很久
+        p.on_wake(task(2, 0));
+        assert_eq!(p.pick_next(), Some(9));
+        p.on_ready(task(9, 3));
+        assert_eq!(drain(&mut p), [2, 1, 9]);
+    }
+
+    #[test]
+    fn edf_earliest_deadline_first() {
+        let now = Instant::now();
+        let at = |id, ms| Task {
+            id,
+            priority: 0,
+            deadline: Some(now + Duration::from_millis(ms)),
+        };
+        let mut edf = Edf::new();
+        edf.on_ready(task(1, 0));
+        edf.on_ready(at(2, 30));
+        edf.on_ready(at(3, 10));
+        edf.on_ready(at(4, 30));
+        edf.on_ready(task(5, 0));
+        edf.on_ready(at(6, 20));
+        edf.on_block(6);
+        assert_eq!(drain(&mut edf), [3, 2, 4, 1, 5]);
+    }
+}
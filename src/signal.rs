@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::mem;
+use std::ptr;
+use std::sync::Once;
+
+use crate::multi;
+use crate::runtime::try_current_runtime;
+use crate::stack::Stack;
+
+// 信号处理函数自己运行在这块栈上，溢出的协程栈已经没有空间了
+const ALT_STACK_SIZE: usize = 64 * 1024;
+
+static INSTALL: Once = Once::new();
+static mut PREVIOUS: mem::MaybeUninit<libc::sigaction> = mem::MaybeUninit::uninit();
+
+thread_local! {
+    static ALT_STACK: RefCell<Option<AltStack>> = const { RefCell::new(None) };
+}
+
+// 线程退出时关闭 sigaltstack 再释放内存
+struct AltStack {
+    _stack: Stack,
+}
+
+impl Drop for AltStack {
+    fn drop(&mut self) {
+        unsafe {
+            let disable = libc::stack_t {
+                ss_sp: ptr::null_mut(),
+                ss_flags: libc::SS_DISABLE,
+                ss_size: ALT_STACK_SIZE,
+            };
+            libc::sigaltstack(&disable, ptr::null_mut());
+        }
+    }
+}
+
+/// 安装 SIGSEGV 处理函数（整个进程一次），并保证当前 OS 线程有可用的 sigaltstack
+pub(crate) fn install_overflow_handler() {
+    INSTALL.call_once(|| unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(
+            libc::SIGSEGV,
+            &action,
+            (&raw mut PREVIOUS).cast::<libc::sigaction>(),
+        );
+    });
+
+    ALT_STACK.with(|alt| {
+        let mut alt = alt.borrow_mut();
+        if alt.is_some() {
+            return;
+        }
+
+        unsafe {
+            // std 已经给当前线程设置过 sigaltstack 的话直接复用
+            let mut current: libc::stack_t = mem::zeroed();
+            libc::sigaltstack(ptr::null(), &mut current);
+            if current.ss_flags & libc::SS_DISABLE == 0 {
+                return;
+            }
+
+            let Ok(stack) = Stack::new(ALT_STACK_SIZE) else {
+                return;
+            };
+            let new = libc::stack_t {
+                ss_sp: stack.top().sub(ALT_STACK_SIZE) as *mut libc::c_void,
+                ss_flags: 0,
+                ss_size: ALT_STACK_SIZE,
+            };
+            if libc::sigaltstack(&new, ptr::null_mut()) == 0 {
+                *alt = Some(AltStack { _stack: stack });
+            }
+        }
+    });
+}
+
+// 只能使用 async-signal-safe 的操作：不分配内存，直接 write(2)
+extern "C" fn handler(signum: libc::c_int, info: *mut libc::siginfo_t, _ctx: *mut libc::c_void) {
+    unsafe {
+        let addr = (*info).si_addr() as usize;
+
+        let overflowed = try_current_runtime()
+            .and_then(|rt| rt.overflowed_thread(addr))
+            .or_else(|| multi::overflowed_coroutine(addr).map(|(id, size)| (id, None, size)));
+        if let Some((id, name, size)) = overflowed {
+            let mut buf = [0_u8; 256];
+            let capacity = buf.len();
+            let mut cursor = &mut buf[..];
+            let _ = match name {
+                Some(name) => writeln!(
+                    cursor,
+                    "coroutine {} ({}) overflowed its {}-byte stack",
+                    id, name, size
+                ),
+                None => writeln!(
+                    cursor,
+                    "coroutine {} overflowed its {}-byte stack",
+                    id, size
+                ),
+            };
+            let len = capacity - cursor.len();
+            libc::write(
+                libc::STDERR_FILENO,
+                buf.as_ptr() as *const libc::c_void,
+                len,
+            );
+            libc::abort();
+        }
+
+        // 不是协程栈溢出：恢复之前的处理函数（比如 std 的主线程栈溢出检测），返回后重新触发
+        libc::sigaction(
+            signum,
+            (&raw const PREVIOUS).cast::<libc::sigaction>(),
+            ptr::null_mut(),
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::io;
+use std::ptr;
+
+/// 协程栈：用 mmap 分配，最低地址留一个 PROT_NONE 的 guard page
+///
+/// 栈向低地址增长，溢出时会先碰到 guard page，产生确定的 SIGSEGV 而不是悄悄改写堆内存
+///
+/// ```text
+/// base                base + page_size                      base + len
+/// | guard (PROT_NONE) | usable (PROT_READ | PROT_WRITE) ...  |
+///                     ^ bottom                               ^ top
+/// ```
+pub(crate) struct Stack {
+    base: *mut u8, // mmap 返回的起始地址，包括 guard page
+    len: usize,    // 映射的总长度，包括 guard page
+    painted: bool, // 可用区域是否填满了 PAINT，只有这时 used() 才有意义
+}
+
+// 用来测量栈使用量的填充值，没被改写过的位置仍然是这个值
+const PAINT: u64 = 0xDEAD_BEEF_C0DE_F00D;
+
+pub(crate) fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// 实际分配的可用栈大小：向上取整到页，至少一页
+pub(crate) fn round_size(size: usize) -> usize {
+    let page = page_size();
+    size.div_ceil(page).max(1) * page
+}
+
+impl Stack {
+    /// 分配至少 `size` 字节的可用栈空间，大小向上取整到页
+    pub(crate) fn new(size: usize) -> io::Result<Stack> {
+        let page = page_size();
+        let len = round_size(size) + page;
+
+        unsafe {
+            // MAP_NORESERVE: 只有真正用到的页才会占用物理内存
+            let base = libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            );
+            if base == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            let stack = Stack {
+                base: base as *mut u8,
+                len,
+                painted: false,
+            };
+            if libc::mprotect(base, page, libc::PROT_NONE) != 0 {
+                return Err(io::Error::last_os_error()); // stack 的 drop 会 munmap
+            }
+            Ok(stack)
+        }
+    }
+
+    /// 栈顶（最高地址），新协程从这里向下使用
+    pub(crate) fn top(&self) -> *mut u8 {
+        unsafe { self.base.add(self.len) }
+    }
+
+    /// 可用区域的最低地址，紧挨着 guard page
+    fn bottom(&self) -> *mut u8 {
+        unsafe { self.base.add(page_size()) }
+    }
+
+    /// 可用字节数，不包括 guard page
+    pub(crate) fn size(&self) -> usize {
+        self.len - page_size()
+    }
+
+    /// 把用过的页还给内核，映射保持不变，下次访问时重新得到全零的页
+    pub(crate) fn release(&mut self) {
+        unsafe {
+            libc::madvise(
+                self.bottom() as *mut libc::c_void,
+                self.size(),
+                libc::MADV_DONTNEED,
+            );
+        }
+        self.painted = false;
+    }
+
+    /// 用 PAINT 填满可用区域，之后可以用 `used` 测量栈的最高水位
+    ///
+    /// 整个栈的页都会被真正分配出来，所以只在需要测量的时候使用
+    pub(crate) fn paint(&mut self) {
+        let words = self.size() / 8;
+        let bottom = self.bottom() as *mut u64;
+        for i in 0..words {
+            unsafe { bottom.add(i).write_volatile(PAINT) };
+        }
+        self.painted = true;
+    }
+
+    /// 从栈底往上找第一个被改写过的位置，返回历史上最多用过多少字节
+    pub(crate) fn used(&self) -> Option<usize> {
+        if !self.painted {
+            return None;
+        }
+        let words = self.size() / 8;
+        let bottom = self.bottom() as *const u64;
+        let untouched = (0..words)
+            .take_while(|&i| unsafe { bottom.add(i).read_volatile() } == PAINT)
+            .count();
+        Some(self.size() - untouched * 8)
+    }
+
+    /// `addr` 是否落在 guard page 内，也就是栈溢出
+    pub(crate) fn guard_contains(&self, addr: usize) -> bool {
+        let base = self.base as usize;
+        addr >= base && addr < base + page_size()
+    }
+}
+
+impl Drop for Stack {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.len);
+        }
+    }
+}
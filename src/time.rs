@@ -0,0 +1,124 @@
+//! 协程睡眠、周期定时器和超时
+//!
+//! 睡眠中的协程不占用调度，定时器到期后重新变成 Ready。只能在单线程的 `Runtime` 中使用。
+//!
+//! ```ignore
+//! let mut ticks = time::interval(Duration::from_millis(16));
+//! loop {
+//!     ticks.tick();
+//!     step();
+//! }
+//!
+//! // Elapsed 可以转换成 io::ErrorKind::TimedOut
+//! let n = time::timeout(Duration::from_secs(1), || stream.read(&mut buf))??;
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+use crate::runtime::{TimedOut, current_runtime};
+
+/// 让当前协程睡眠至少 `duration`，精度 1 毫秒
+pub fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration);
+}
+
+/// 让当前协程睡眠到 `deadline`，时间已经过了的话只让出一次执行权
+pub fn sleep_until(deadline: Instant) {
+    let rt = current_runtime();
+    if deadline <= Instant::now() {
+        rt.t_yield();
+        return;
+    }
+    rt.t_sleep_until(deadline);
+}
+
+/// 每隔 `period` 触发一次的定时器，第一次 `tick` 立即返回
+pub fn interval(period: Duration) -> Interval {
+    assert!(!period.is_zero(), "interval period must be non-zero");
+    Interval {
+        next: Instant::now(),
+        period,
+    }
+}
+
+/// `interval` 返回的周期定时器
+#[derive(Debug)]
+pub struct Interval {
+    next: Instant,
+    period: Duration,
+}
+
+impl Interval {
+    /// 睡到下一个触发时间，返回这个触发时间
+    ///
+    /// 错过的触发不会补上：下一个触发时间已经过了的话从现在开始重新计时
+    pub fn tick(&mut self) -> Instant {
+        let deadline = self.next;
+        sleep_until(deadline);
+
+        self.next = deadline + self.period;
+        let now = Instant::now();
+        if self.next <= now {
+            self.next = now + self.period;
+        }
+        deadline
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// 运行 `f`，超过 `duration` 还没有完成的话放弃它并返回 `Err(Elapsed)`
+///
+/// 到期时如果 `f` 阻塞在 park、join、sleep、fd 等待、`spawn_blocking` 或者 io_uring 操作上，
+/// 等待被撤销，协程展开 `f` 的栈（执行 Drop）后从这里返回。
+/// `f` 不阻塞的话不会被打断，下一次阻塞或者让出执行权时才会退出
+pub fn timeout<F, T>(duration: Duration, f: F) -> Result<T, Elapsed>
+where
+    F: FnOnce() -> T,
+{
+    timeout_at(Instant::now() + duration, f)
+}
+
+/// 和 `timeout` 相同，到 `deadline` 为止
+pub fn timeout_at<F, T>(deadline: Instant, f: F) -> Result<T, Elapsed>
+where
+    F: FnOnce() -> T,
+{
+    let timer = current_runtime().t_start_timeout(deadline);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    current_runtime().t_end_timeout(timer);
+
+    match result {
+        Ok(value) => Ok(value),
+        Err(payload) => match payload.downcast::<TimedOut>() {
+            Ok(timed_out) if timed_out.0 == timer => Err(Elapsed(())),
+            // 外层的 timeout 到期，或者其他 panic、取消
+            Ok(timed_out) => panic::resume_unwind(timed_out),
+            Err(payload) => panic::resume_unwind(payload),
+        },
+    }
+}
+
+/// `timeout` 到期时返回的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+    fn from(elapsed: Elapsed) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, elapsed)
+    }
+}
@@ -0,0 +1,345 @@
+use std::time::{Duration, Instant};
+
+// 每一级 64 个槽，6 级以毫秒为单位覆盖 2^36 ms（两年多），更远的定时器放在 overflow 里
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+const WHEEL_BITS: u32 = SLOT_BITS * LEVELS as u32;
+
+struct Entry<T> {
+    tick: u64, // 到期的 tick，级联到低层时重新计算槽位
+    value: T,
+}
+
+/// 分层时间轮，精度 1 毫秒
+///
+/// 第 L 级的一个槽覆盖 64^L 个 tick。定时器放在和当前 tick 第一个不同的那一级，
+/// 时间推进到槽的起点时把槽里的定时器重新插入，逐级落到第 0 级后到期。
+/// 插入 O(1)，推进的代价和经过的非空槽数量成正比，和经过的时间无关。
+pub(crate) struct TimerWheel<T> {
+    start: Instant,
+    now: u64, // 已经处理到的 tick
+    levels: Vec<Vec<Vec<Entry<T>>>>,
+    occupied: [u64; LEVELS], // 每一级哪些槽非空
+    overflow: Vec<Entry<T>>, // 不在当前这一圈最高级范围内的定时器
+    due: Vec<T>,             // 插入时已经到期的定时器，下一次 expire 时返回
+    len: usize,
+}
+
+impl<T> TimerWheel<T> {
+    pub(crate) fn new() -> Self {
+        TimerWheel {
+            start: Instant::now(),
+            now: 0,
+            levels: (0..LEVELS)
+                .map(|_| (0..SLOTS).map(|_| Vec::new()).collect())
+                .collect(),
+            occupied: [0; LEVELS],
+            overflow: Vec::new(),
+            due: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // 向上取整，定时器不会提前到期
+    fn tick_of(&self, deadline: Instant) -> u64 {
+        let elapsed = deadline.saturating_duration_since(self.start);
+        let ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        ms.saturating_add(u64::from(!elapsed.subsec_nanos().is_multiple_of(1_000_000)))
+    }
+
+    fn instant_of(&self, tick: u64) -> Instant {
+        self.start + Duration::from_millis(tick)
+    }
+
+    pub(crate) fn insert(&mut self, deadline: Instant, value: T) {
+        let tick = self.tick_of(deadline);
+        self.len += 1;
+        if tick <= self.now {
+            self.due.push(value);
+        } else {
+            self.place(Entry { tick, value });
+        }
+    }
+
+    // entry.tick 必须大于 now
+    fn place(&mut self, entry: Entry<T>) {
+        let (level, slot) = self.locate(entry.tick);
+        if level >= LEVELS {
+            self.overflow.push(entry);
+            return;
+        }
+        self.levels[level][slot].push(entry);
+        self.occupied[level] |= 1 << slot;
+    }
+
+    // tick 所在的 (级, 槽)，级不小于 LEVELS 表示在 overflow 里。
+    // now 不会越过还没处理的槽的起点，所以插入之后定时器的位置一直由 tick 和 now 决定
+    fn locate(&self, tick: u64) -> (usize, usize) {
+        let level = ((63 - (tick ^ self.now).leading_zeros()) / SLOT_BITS) as usize;
+        let slot = (tick >> (SLOT_BITS * level.min(LEVELS - 1) as u32)) as usize & (SLOTS - 1);
+        (level, slot)
+    }
+
+    /// 删除还没有到期的定时器，`deadline` 必须和插入时相同；已经到期返回过的话返回 false
+    pub(crate) fn remove(&mut self, deadline: Instant, value: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let tick = self.tick_of(deadline);
+        let removed = if tick <= self.now {
+            let index = self.due.iter().position(|v| v == value);
+            index.map(|i| self.due.swap_remove(i)).is_some()
+        } else {
+            let (level, slot) = self.locate(tick);
+            let entries = if level >= LEVELS {
+                &mut self.overflow
+            } else {
+                &mut self.levels[level][slot]
+            };
+            let index = entries
+                .iter()
+                .position(|e| e.tick == tick && e.value == *value);
+            let removed = index.map(|i| entries.swap_remove(i)).is_some();
+            if level < LEVELS && entries.is_empty() {
+                self.occupied[level] &= !(1 << slot);
+            }
+            removed
+        };
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    // 下一个需要处理的槽：(级, 槽, 槽的起点 tick)。低层的槽总是比高层的早，
+    // 时间轮空了才轮到 overflow（级别记为 LEVELS），在下一圈开始时重新插入
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        let Some(level) = self.occupied.iter().position(|&bits| bits != 0) else {
+            return (!self.overflow.is_empty())
+                .then(|| (LEVELS, 0, ((self.now >> WHEEL_BITS) + 1) << WHEEL_BITS));
+        };
+        let slot = self.occupied[level].trailing_zeros() as usize;
+        let shift = SLOT_BITS * level as u32;
+        let base = self.now >> (shift + SLOT_BITS) << (shift + SLOT_BITS);
+        Some((level, slot, base + ((slot as u64) << shift)))
+    }
+
+    /// 最近的定时器到期时间（可能是级联的时间点，早于真正的到期时间）
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        if !self.due.is_empty() {
+            return Some(self.instant_of(self.now));
+        }
+        self.next_slot().map(|(_, _, tick)| self.instant_of(tick))
+    }
+
+    /// 推进到 `now`，返回所有到期的定时器
+    pub(crate) fn expire(&mut self, now: Instant) -> Vec<T> {
+        let target = now.saturating_duration_since(self.start).as_millis() as u64;
+        let mut expired = std::mem::take(&mut self.due);
+
+        while let Some((level, slot, tick)) = self.next_slot() {
+            if tick > target {
+                break;
+            }
+            self.now = tick;
+            let entries = if level == LEVELS {
+                std::mem::take(&mut self.overflow)
+            } else {
+                self.occupied[level] &= !(1 << slot);
+                std::mem::take(&mut self.levels[level][slot])
+            };
+            for entry in entries {
+                if entry.tick <= self.now {
+                    expired.push(entry.value);
+                } else {
+                    self.place(entry); // 级联到更低的一级
+                }
+            }
+        }
+
+        self.now = self.now.max(target);
+        self.len -= expired.len();
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(wheel: &TimerWheel<u32>, ms: u64) -> Instant {
+        wheel.start + Duration::from_millis(ms)
+    }
+
+    fn sorted(mut values: Vec<u32>) -> Vec<u32> {
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn deadline_rounds_up_to_next_tick() {
+        let mut wheel = TimerWheel::new();
+        wheel.insert(wheel.start + Duration::from_micros(1500), 1);
+        assert_eq!(wheel.next_deadline(), Some(ms(&wheel, 2)));
+        assert!(
+            wheel
+                .expire(wheel.start + Duration::from_micros(1999))
+                .is_empty()
+        );
+        assert_eq!(wheel.expire(ms(&wheel, 2)), [1]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn past_deadline_is_due_immediately() {
+        let mut wheel = TimerWheel::new();
+        assert!(wheel.expire(ms(&wheel, 10)).is_empty());
+        wheel.insert(ms(&wheel, 3), 1);
+        wheel.insert(ms(&wheel, 10), 2);
+        assert_eq!(wheel.next_deadline(), Some(ms(&wheel, 10)));
+        assert_eq!(sorted(wheel.expire(ms(&wheel, 10))), [1, 2]);
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn cascades_through_levels() {
+        let mut wheel = TimerWheel::new();
+        // 分别落在第 0、1、2、3 级
+        for (value, tick) in [(1, 10), (2, 100), (3, 5_000), (4, 300_000)] {
+            wheel.insert(ms(&wheel, tick), value);
+        }
+        assert_eq!(wheel.expire(ms(&wheel, 10)), [1]);
+        assert!(wheel.expire(ms(&wheel, 99)).is_empty());
+        assert_eq!(wheel.expire(ms(&wheel, 100)), [2]);
+        assert!(wheel.expire(ms(&wheel, 4_999)).is_empty());
+        assert_eq!(wheel.expire(ms(&wheel, 5_000)), [3]);
+        assert!(wheel.expire(ms(&wheel, 299_999)).is_empty());
+        assert_eq!(wheel.expire(ms(&wheel, 300_000)), [4]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn overflow_is_reinserted_on_later_rounds() {
+        let mut wheel = TimerWheel::new();
+        let round = 1_u64 << WHEEL_BITS;
+        wheel.insert(ms(&wheel, round + 5), 1);
+        wheel.insert(ms(&wheel, 3 * round + 7), 2);
+        wheel.insert(ms(&wheel, 20), 3);
+        assert_eq!(wheel.overflow.len(), 2);
+
+        assert_eq!(wheel.expire(ms(&wheel, 20)), [3]);
+        // 下一个需要处理的时间点是下一圈的开始，不能晚于真正的到期时间
+        assert_eq!(wheel.next_deadline(), Some(ms(&wheel, round)));
+        assert!(wheel.expire(ms(&wheel, round + 4)).is_empty());
+        assert_eq!(wheel.expire(ms(&wheel, round + 5)), [1]);
+        assert!(wheel.expire(ms(&wheel, 3 * round + 6)).is_empty());
+        assert_eq!(wheel.expire(ms(&wheel, 3 * round + 7)), [2]);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn removed_timers_do_not_expire() {
+        let mut wheel = TimerWheel::new();
+        let deadlines = [3, 10, 100, 5_000, (1 << WHEEL_BITS) + 5];
+        for (value, &tick) in deadlines.iter().enumerate() {
+            wheel.insert(ms(&wheel, tick), value as u32);
+        }
+        assert_eq!(wheel.expire(ms(&wheel, 3)), [0]);
+        // 已经到期的找不到，截止时间不对的也找不到
+        assert!(!wheel.remove(ms(&wheel, 3), &0));
+        assert!(!wheel.remove(ms(&wheel, 11), &1));
+        for (value, &tick) in deadlines.iter().enumerate().skip(1) {
+            assert!(wheel.remove(ms(&wheel, tick), &(value as u32)));
+        }
+        assert!(wheel.is_empty());
+        assert_eq!(wheel.occupied, [0; LEVELS]);
+        assert_eq!(wheel.next_deadline(), None);
+
+        // 插入时已经到期的在 due 里
+        wheel.insert(ms(&wheel, 1), 7);
+        assert!(wheel.remove(ms(&wheel, 1), &7));
+        assert!(wheel.expire(ms(&wheel, 1 << 20)).is_empty());
+    }
+
+    // xorshift64*，测试不需要引入随机数依赖
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        // 各种数量级都要覆盖到：同一个槽、跨级、跨圈
+        fn span(&mut self) -> u64 {
+            let bits = self.next() % 42;
+            self.next() % (1 << bits)
+        }
+    }
+
+    #[test]
+    fn matches_reference_on_random_operations() {
+        for seed in 1..=20 {
+            let mut rng = Rng(0x9e37_79b9_7f4a_7c15_u64.wrapping_mul(seed));
+            let mut wheel = TimerWheel::new();
+            let mut reference: Vec<(u64, u32, Instant)> = Vec::new(); // (到期 tick, 值, 截止时间)
+            let mut expired_before: Vec<(u64, u32, Instant)> = Vec::new();
+            let mut now_us = 0_u64;
+            let mut next_value = 0;
+
+            for _ in 0..3_000 {
+                let op = rng.next() % 6;
+                if op >= 3 {
+                    // 以微秒为单位的截止时间，检查向上取整
+                    let deadline_us = (now_us + rng.span()).saturating_sub(rng.next() % 2_000);
+                    let deadline = wheel.start + Duration::from_micros(deadline_us);
+                    wheel.insert(deadline, next_value);
+                    reference.push((deadline_us.div_ceil(1_000), next_value, deadline));
+                    next_value += 1;
+                } else if op == 2 {
+                    // 删除还在等待的定时器，或者已经到期的（找不到）
+                    if !reference.is_empty() && !rng.next().is_multiple_of(4) {
+                        let index = (rng.next() % reference.len() as u64) as usize;
+                        let (_, value, deadline) = reference.swap_remove(index);
+                        assert!(wheel.remove(deadline, &value), "seed {}", seed);
+                    } else if !expired_before.is_empty() {
+                        let index = (rng.next() % expired_before.len() as u64) as usize;
+                        let (_, value, deadline) = expired_before[index];
+                        assert!(!wheel.remove(deadline, &value), "seed {}", seed);
+                    }
+                    assert_eq!(wheel.is_empty(), reference.is_empty(), "seed {}", seed);
+                } else {
+                    now_us += rng.span();
+                    let now = wheel.start + Duration::from_micros(now_us);
+                    let target = now_us / 1_000;
+
+                    // 已经过期的定时器在当前 tick 返回，其他的不能晚于真正的到期时间
+                    if let (Some(next), Some(&(first, _, _))) =
+                        (wheel.next_deadline(), reference.iter().min())
+                    {
+                        assert!(next <= ms(&wheel, first.max(wheel.now)), "seed {}", seed);
+                    }
+                    let expired = sorted(wheel.expire(now));
+                    let mut expected: Vec<u32> = reference
+                        .iter()
+                        .filter(|&&(tick, _, _)| tick <= target)
+                        .map(|&(_, value, _)| value)
+                        .collect();
+                    expected.sort_unstable();
+                    expired_before.extend(reference.iter().filter(|&&(tick, _, _)| tick <= target));
+                    reference.retain(|&(tick, _, _)| tick > target);
+                    assert_eq!(expired, expected, "seed {}", seed);
+                    assert_eq!(wheel.is_empty(), reference.is_empty(), "seed {}", seed);
+                }
+            }
+        }
+    }
+}
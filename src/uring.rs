@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+
+use io_uring::{IoUring, opcode, squeue};
+
+use crate::reactor::Reactor;
+use crate::runtime::Waiter;
+
+// 提交队列的长度，满了先提交再继续放
+const ENTRIES: u32 = 256;
+
+// AsyncCancel 自己的完成事件的 user_data，和操作编号区分
+const CANCEL: u64 = u64::MAX;
+
+struct Op {
+    waiter: Waiter,
+    result: Option<i32>, // CQE 的结果，负数是 -errno
+}
+
+/// io_uring 驱动：协程提交 SQE 后 park，完成队列中的结果交给对应的协程
+///
+/// ring 的 fd 登记在反应器中，完成队列非空时 epoll_wait 返回，和定时器、fd 等待一起处理
+pub(crate) struct Uring {
+    ring: Option<IoUring>, // 第一次提交时创建
+    ops: HashMap<u64, Op>,
+    next: u64,
+    in_flight: usize,   // 还没有完成的操作
+    woken: Vec<Waiter>, // 已经完成、还没有交给 runtime 唤醒的等待者
+}
+
+impl Uring {
+    pub(crate) fn new() -> Self {
+        Uring {
+            ring: None,
+            ops: HashMap::new(),
+            next: 0,
+            in_flight: 0,
+            woken: Vec::new(),
+        }
+    }
+
+    /// 在等完成事件或者等着被唤醒的线程数量
+    pub(crate) fn waiters(&self) -> usize {
+        self.in_flight + self.woken.len()
+    }
+
+    fn ring(&mut self) -> &mut IoUring {
+        self.ring
+            .as_mut()
+            .expect("io_uring is created on first submit")
+    }
+
+    /// 提交一个操作，完成时唤醒 waiter；返回操作编号
+    ///
+    /// # Safety
+    ///
+    /// 操作引用的内存在完成之前必须一直有效，提前放弃等待的话先调用 `cancel`
+    pub(crate) unsafe fn submit(
+        &mut self,
+        reactor: &mut Reactor,
+        entry: squeue::Entry,
+        waiter: Waiter,
+    ) -> io::Result<u64> {
+        if self.ring.is_none() {
+            let ring = IoUring::new(ENTRIES)?;
+            reactor.watch(ring.as_raw_fd())?;
+            self.ring = Some(ring);
+        }
+
+        let token = self.next;
+        self.next += 1;
+        unsafe { self.push(&entry.user_data(token)) };
+        self.ops.insert(
+            token,
+            Op {
+                waiter,
+                result: None,
+            },
+        );
+        self.in_flight += 1;
+        self.enter(0);
+        Ok(token)
+    }
+
+    // 放进提交队列，满了先提交
+    unsafe fn push(&mut self, entry: &squeue::Entry) {
+        while unsafe { self.ring().submission().push(entry) }.is_err() {
+            self.enter(0);
+        }
+    }
+
+    // 提交队列中的操作，并等待至少 want 个完成事件
+    fn enter(&mut self, want: usize) {
+        loop {
+            match self.ring().submit_and_wait(want) {
+                Ok(_) => return,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // 完成队列满了，先把完成事件取走
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => self.drain(),
+                Err(e) => panic!("io_uring_enter failed: {}", e),
+            }
+        }
+    }
+
+    // 取出完成队列中的事件，结果交给对应的操作
+    fn drain(&mut self) {
+        let Some(ring) = &mut self.ring else {
+            return;
+        };
+        for cqe in ring.completion() {
+            if let Some(op) = self.ops.get_mut(&cqe.user_data())
+                && op.result.is_none()
+            {
+                op.result = Some(cqe.result());
+                self.in_flight -= 1;
+                self.woken.push(op.waiter);
+            }
+        }
+    }
+
+    /// 不阻塞地取出完成的操作，返回需要唤醒的等待者
+    pub(crate) fn complete(&mut self) -> Vec<Waiter> {
+        self.drain();
+        mem::take(&mut self.woken)
+    }
+
+    /// 取走已经完成的操作的结果
+    pub(crate) fn take_result(&mut self, token: u64) -> Option<i32> {
+        let result = self.ops.get(&token)?.result?;
+        self.ops.remove(&token);
+        Some(result)
+    }
+
+    /// 放弃等待操作，比如等待的协程被取消；内核确认操作结束之后才返回，
+    /// 因为操作引用的内存马上就要释放了。已经取走结果的话什么都不做
+    pub(crate) fn cancel(&mut self, token: u64) {
+        let Some(op) = self.ops.get(&token) else {
+            return;
+        };
+        let waiter = op.waiter;
+        if op.result.is_none() {
+            let entry = opcode::AsyncCancel::new(token).build().user_data(CANCEL);
+            unsafe { self.push(&entry) };
+            while self.ops[&token].result.is_none() {
+                self.enter(1);
+                self.drain();
+            }
+        }
+        self.ops.remove(&token);
+        // 等待者不会再 park 等这个结果，不能给它留下唤醒
+        self.woken.retain(|&w| w != waiter);
+    }
+}
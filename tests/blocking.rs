@@ -0,0 +1,123 @@
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustcoro::net::UdpSocket;
+use rustcoro::{Runtime, park, spawn, spawn_blocking, time, yield_now};
+
+// 每个测试在自己的 OS 线程上运行，各自创建 runtime
+fn runtime() -> Runtime {
+    let mut rt = Runtime::new();
+    rt.init();
+    rt
+}
+
+// 当前 OS 线程用掉的 CPU 时间，runtime 在测试线程上运行
+fn thread_cpu_time() -> Duration {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) },
+        0
+    );
+    let micros = |tv: libc::timeval| tv.tv_sec as u64 * 1_000_000 + tv.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+#[test]
+fn result_arrives_while_other_coroutines_keep_running() {
+    let mut rt = runtime();
+    let ticks = Rc::new(Cell::new(0));
+    let done = Rc::new(Cell::new(false));
+
+    {
+        let (ticks, done) = (Rc::clone(&ticks), Rc::clone(&done));
+        spawn(move || {
+            while !done.get() {
+                ticks.set(ticks.get() + 1);
+                time::sleep(Duration::from_millis(1));
+            }
+        });
+    }
+
+    let caller = {
+        let ticks = Rc::clone(&ticks);
+        spawn(move || {
+            let value = spawn_blocking(|| {
+                thread::sleep(Duration::from_millis(50));
+                thread::current().name().map(str::to_owned)
+            });
+            done.set(true);
+            (value, ticks.get())
+        })
+    };
+
+    let (name, seen) = caller.join();
+    assert_eq!(name.as_deref(), Some("rustcoro-blocking"));
+    // 等待期间另一个协程一直在运行
+    assert!(seen > 1, "{}", seen);
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn panic_in_the_pool_is_raised_in_the_caller() {
+    let mut rt = runtime();
+    let caller = spawn(|| {
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| {
+            spawn_blocking(|| panic!("in the pool"))
+        }))
+        .unwrap_err();
+        *payload.downcast::<&str>().unwrap()
+    });
+
+    assert_eq!(caller.join(), "in the pool");
+    // 线程池不受影响
+    assert_eq!(spawn(|| spawn_blocking(|| 1 + 1)).join(), 2);
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn abandoned_wait_does_not_spin_or_hang_run() {
+    let mut rt = runtime();
+
+    // 超时放弃的任务在线程池中继续运行，完成后 eventfd 变成可读
+    spawn(|| {
+        let waited = time::timeout(Duration::from_millis(5), || {
+            spawn_blocking(|| thread::sleep(Duration::from_millis(20)))
+        });
+        assert!(waited.is_err());
+    });
+    // 被取消的等待
+    let cancelled = spawn(|| spawn_blocking(|| thread::sleep(Duration::from_millis(20))));
+    spawn(move || {
+        yield_now();
+        assert!(cancelled.cancel());
+    });
+    // 同时有协程在等 fd，run 阻塞在 epoll_wait 中；eventfd 一直可读的话 epoll_wait 立即返回
+    spawn(|| {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let received = time::timeout(Duration::from_millis(300), || socket.recv(&mut [0; 16]));
+        assert!(received.is_err());
+    });
+
+    let (start, cpu) = (Instant::now(), thread_cpu_time());
+    let summary = rt.run();
+    let (elapsed, cpu) = (start.elapsed(), thread_cpu_time() - cpu);
+    assert_eq!(summary.tasks, 4);
+    assert_eq!(summary.panics, 0);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    assert!(
+        cpu < Duration::from_millis(150),
+        "run used {:?} of CPU",
+        cpu
+    );
+
+    // 放弃的任务不会唤醒任何协程，也不会留下许可
+    let err = panic::catch_unwind(park).unwrap_err();
+    assert!(
+        err.downcast_ref::<String>()
+            .unwrap()
+            .starts_with("deadlock")
+    );
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rustcoro::{MultiRuntime, SpawnError, spawn, yield_now};
+
+#[test]
+fn yielding_tasks_spawn_more_tasks_across_workers() {
+    const TASKS: usize = 64;
+    const CHILDREN: usize = 3;
+    const STEPS: usize = 20;
+
+    let rt = MultiRuntime::new(4);
+    let steps = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..TASKS {
+        let steps = Arc::clone(&steps);
+        // 协程中只有 Arc 和原子操作，没有跨 yield_now 的 !Send 状态
+        unsafe {
+            rt.spawn(move || {
+                for _ in 0..STEPS {
+                    steps.fetch_add(1, Ordering::Relaxed);
+                    yield_now();
+                }
+                for _ in 0..CHILDREN {
+                    let steps = Arc::clone(&steps);
+                    MultiRuntime::spawnf(move || {
+                        for _ in 0..STEPS {
+                            steps.fetch_add(1, Ordering::Relaxed);
+                            yield_now();
+                        }
+                    })
+                    .unwrap();
+                }
+            })
+        }
+        .unwrap();
+    }
+    // run 之前 spawn 的 panic 只计入统计
+    unsafe { rt.spawn(|| panic!("worker task")) }.unwrap();
+
+    let summary = rt.run();
+    assert_eq!(summary.tasks, TASKS * (1 + CHILDREN) + 1);
+    assert_eq!(summary.panics, 1);
+    assert_eq!(
+        steps.load(Ordering::Relaxed),
+        TASKS * (1 + CHILDREN) * STEPS
+    );
+    assert!(summary.switches >= (TASKS * (1 + CHILDREN) * STEPS) as u64);
+
+    // 同一个 MultiRuntime 可以再次 run
+    unsafe { rt.spawn(yield_now) }.unwrap();
+    assert_eq!(rt.run().tasks, 1);
+}
+
+#[test]
+fn single_threaded_spawn_is_rejected_inside_workers() {
+    let rt = MultiRuntime::new(2);
+    let rejected = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&rejected);
+    unsafe {
+        rt.spawn(move || {
+            if let Err(SpawnError::MultiRuntime) = rustcoro::Builder::new().spawn(|| ()) {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+            // rustcoro::spawn 会 panic
+            let _ = spawn(|| ());
+        })
+    }
+    .unwrap();
+
+    let summary = rt.run();
+    assert_eq!(rejected.load(Ordering::Relaxed), 1);
+    assert_eq!(summary.panics, 1);
+}
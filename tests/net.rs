@@ -0,0 +1,253 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::AsFd;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rustcoro::net::{TcpListener, TcpStream, UdpSocket, UnixDatagram, UnixListener, UnixStream};
+use rustcoro::{Runtime, spawn};
+
+// 每个测试在自己的 OS 线程上运行，各自创建 runtime
+fn runtime() -> Runtime {
+    let mut rt = Runtime::new();
+    rt.init();
+    rt
+}
+
+// 测试用的 socket 路径，每个进程、每个测试不同
+fn socket_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rustcoro-{}-{}.sock", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+// 大于 socket 缓冲区，读写双方都要等 fd 就绪
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[test]
+fn tcp_echo_over_localhost() {
+    const CLIENTS: usize = 3;
+    const LEN: usize = 1 << 20;
+
+    let mut rt = runtime();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // 每个连接一个协程
+    spawn(move || {
+        for _ in 0..CLIENTS {
+            let (mut stream, _) = listener.accept().unwrap();
+            spawn(move || {
+                let mut buf = [0; 4096];
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    stream.write_all(&buf[..n]).unwrap();
+                }
+            });
+        }
+    });
+
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|_| {
+            spawn(move || {
+                let stream = Rc::new(TcpStream::connect(addr).unwrap());
+                assert_eq!(stream.peer_addr().unwrap(), addr);
+
+                // 一个协程写，当前协程读，同一个连接的两个方向同时等待
+                let writer = Rc::clone(&stream);
+                let sent = spawn(move || {
+                    (&*writer).write_all(&payload(LEN)).unwrap();
+                    writer.shutdown(Shutdown::Write).unwrap();
+                });
+
+                let mut echoed = Vec::new();
+                (&*stream).read_to_end(&mut echoed).unwrap();
+                sent.join();
+                echoed
+            })
+        })
+        .collect();
+
+    for client in clients {
+        assert!(client.join() == payload(LEN));
+    }
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn tcp_connect_refused() {
+    let _rt = runtime();
+    // 先占一个端口再关掉，连接会被拒绝
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let err = spawn(move || TcpStream::connect(addr).unwrap_err()).join();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn udp_round_trip_between_coroutines() {
+    const ROUNDS: u32 = 100;
+
+    let mut rt = runtime();
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    // 服务端先开始等，recv_from 要让出执行权给客户端
+    spawn(move || {
+        let mut buf = [0; 64];
+        for _ in 0..ROUNDS {
+            let (n, from) = server.recv_from(&mut buf).unwrap();
+            let value = u32::from_le_bytes(buf[..n].try_into().unwrap());
+            server.send_to(&(value * 2).to_le_bytes(), from).unwrap();
+        }
+    });
+
+    let client = spawn(move || {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server_addr).unwrap();
+        assert_eq!(socket.peer_addr().unwrap(), server_addr);
+        let mut buf = [0; 64];
+        (0..ROUNDS)
+            .map(|i| {
+                socket.send(&i.to_le_bytes()).unwrap();
+                let n = socket.recv(&mut buf).unwrap();
+                u32::from_le_bytes(buf[..n].try_into().unwrap())
+            })
+            .sum::<u32>()
+    });
+
+    assert_eq!(client.join(), ROUNDS * (ROUNDS - 1));
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn unix_stream_echo() {
+    const LEN: usize = 1 << 20;
+
+    let mut rt = runtime();
+    let path = socket_path("stream");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 4096];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            stream.write_all(&buf[..n]).unwrap();
+        }
+    });
+
+    let client = {
+        let path = path.clone();
+        spawn(move || {
+            let stream = Rc::new(UnixStream::connect(&path).unwrap());
+            let writer = Rc::clone(&stream);
+            let sent = spawn(move || {
+                (&*writer).write_all(&payload(LEN)).unwrap();
+                writer.shutdown(Shutdown::Write).unwrap();
+            });
+
+            let mut echoed = Vec::new();
+            (&*stream).read_to_end(&mut echoed).unwrap();
+            sent.join();
+            echoed
+        })
+    };
+
+    assert!(client.join() == payload(LEN));
+    assert_eq!(rt.run().panics, 0);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unix_stream_connect_errors() {
+    let _rt = runtime();
+    let missing = socket_path("missing");
+    let err = UnixStream::connect(&missing).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    let too_long = std::env::temp_dir().join("x".repeat(200));
+    let err = UnixStream::connect(too_long).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn unix_datagram_send_to_and_recv_from() {
+    let mut rt = runtime();
+    let server_path = socket_path("datagram-server");
+    let client_path = socket_path("datagram-client");
+    let server = UnixDatagram::bind(&server_path).unwrap();
+
+    spawn(move || {
+        let mut buf = [0; 64];
+        let (n, from) = server.recv_from(&mut buf).unwrap();
+        let reply: Vec<u8> = buf[..n].iter().rev().copied().collect();
+        server.send_to(&reply, from.as_pathname().unwrap()).unwrap();
+    });
+
+    let client = {
+        let (server_path, client_path) = (server_path.clone(), client_path.clone());
+        spawn(move || {
+            let socket = UnixDatagram::bind(&client_path).unwrap();
+            socket.send_to(b"ping", &server_path).unwrap();
+            let mut buf = [0; 64];
+            let (n, from) = socket.recv_from(&mut buf).unwrap();
+            assert_eq!(from.as_pathname(), Some(server_path.as_path()));
+            buf[..n].to_vec()
+        })
+    };
+
+    assert_eq!(client.join(), b"gnip");
+    assert_eq!(rt.run().panics, 0);
+    std::fs::remove_file(&server_path).unwrap();
+    std::fs::remove_file(&client_path).unwrap();
+}
+
+#[test]
+fn unix_fd_passing() {
+    let mut rt = runtime();
+    let (stream_a, stream_b) = UnixStream::pair().unwrap();
+    let (datagram_a, datagram_b) = UnixDatagram::pair().unwrap();
+
+    // 接收方先等着，收到 pipe 的写端后通过它写入
+    let receiver = spawn(move || {
+        let mut fds = Vec::new();
+        let mut buf = [0; 16];
+        let n = stream_b.recv_with_fds(&mut buf, &mut fds).unwrap();
+        assert_eq!(&buf[..n], b"fd");
+        let n = datagram_b.recv_with_fds(&mut buf, &mut fds).unwrap();
+        assert_eq!(&buf[..n], b"fds");
+
+        assert_eq!(fds.len(), 3);
+        for (i, fd) in fds.into_iter().enumerate() {
+            write!(File::from(fd), "{}", i).unwrap();
+        }
+    });
+
+    let (mut reader, writer) = io::pipe().unwrap();
+    spawn(move || {
+        let fd = writer.as_fd();
+        stream_a.send_with_fds(b"fd", &[fd]).unwrap();
+        datagram_a.send_with_fds(b"fds", &[fd, fd]).unwrap();
+        // 对端收到的是复制出来的 fd，这里的写端随协程结束关闭，不影响它们
+    });
+
+    receiver.join();
+    assert_eq!(rt.run().panics, 0);
+
+    // 所有写端都已经关闭，pipe 读到 EOF 为止
+    let mut written = String::new();
+    reader.read_to_string(&mut written).unwrap();
+    assert_eq!(written, "012");
+}
@@ -0,0 +1,478 @@
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rustcoro::net::UdpSocket;
+use rustcoro::scheduler::Edf;
+use rustcoro::time;
+use rustcoro::{
+    Builder, Coroutine, Runtime, SpawnError, current, park, set_deadline, spawn, spawn_blocking,
+    yield_now,
+};
+
+// 每个测试在自己的 OS 线程上运行，各自创建 runtime
+fn runtime() -> Runtime {
+    let mut rt = Runtime::new();
+    rt.init();
+    rt
+}
+
+#[test]
+fn run_returns_summary_when_all_coroutines_finish() {
+    let mut rt = runtime();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    for id in 0..3 {
+        let log = Rc::clone(&log);
+        spawn(move || {
+            for step in 0..2 {
+                log.borrow_mut().push((id, step));
+                yield_now();
+            }
+        });
+    }
+
+    let summary = rt.run();
+    assert_eq!(summary.tasks, 3);
+    assert_eq!(summary.panics, 0);
+    assert!(summary.switches >= 3 * 2);
+    assert_eq!(log.borrow().len(), 6);
+
+    // 同一个 runtime 可以再次 spawn 和 run，统计只包括这一次
+    spawn(|| {});
+    let summary = rt.run();
+    assert_eq!(summary.tasks, 1);
+    assert_eq!(rt.run().tasks, 0);
+}
+
+#[test]
+fn join_returns_result() {
+    let mut rt = runtime();
+    let inner = spawn(|| {
+        yield_now();
+        20
+    });
+    let outer = spawn(move || inner.join() + 22);
+    assert_eq!(outer.join(), 42);
+    assert_eq!(rt.run().tasks, 0);
+}
+
+#[test]
+fn join_propagates_panic() {
+    let mut rt = runtime();
+    let handle = spawn(|| -> u32 { panic!("boom") });
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| handle.join())).unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+
+    // 没有 join 的协程 panic 只计入统计，不影响其他协程
+    spawn(|| panic!("detached"));
+    let done = spawn(|| 1);
+    let summary = rt.run();
+    assert_eq!(summary.panics, 1);
+    assert_eq!(done.join(), 1);
+}
+
+#[test]
+fn run_reports_deadlock() {
+    let mut rt = runtime();
+    let _stuck = Builder::new().name("stuck").spawn(park).unwrap();
+
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| rt.run())).unwrap_err();
+    let report = payload.downcast_ref::<String>().unwrap();
+    assert!(report.starts_with("deadlock"), "{}", report);
+    assert!(
+        report.contains("coroutine 1 (stuck) waiting on park()"),
+        "{}",
+        report
+    );
+    assert!(report.contains("tests/runtime.rs"), "{}", report);
+}
+
+#[test]
+fn run_requires_the_registered_runtime() {
+    let mut a = runtime();
+    let handle = spawn(|| 7);
+    let mut b = runtime();
+
+    // 协程属于 a，但线程局部变量指向 b
+    let err = panic::catch_unwind(AssertUnwindSafe(|| a.run())).unwrap_err();
+    assert!(
+        err.downcast_ref::<&str>()
+            .unwrap()
+            .contains("not registered")
+    );
+    assert_eq!(b.run().tasks, 0);
+
+    a.init();
+    assert_eq!(a.run().tasks, 1);
+    assert_eq!(handle.join(), 7);
+}
+
+#[test]
+fn handles_only_act_on_their_own_runtime() {
+    let mut a = runtime();
+    let dropped = spawn(|| 1);
+    let kept = spawn(|| 2);
+    let base_a = rustcoro::current();
+
+    let b = runtime();
+    let err = panic::catch_unwind(AssertUnwindSafe(|| dropped.is_finished())).unwrap_err();
+    assert!(
+        err.downcast_ref::<&str>()
+            .unwrap()
+            .contains("different rustcoro runtime")
+    );
+    // 不能 detach b 中编号相同的槽位，也不能 panic
+    drop(dropped);
+    // 不能给 b 的 base thread 留下许可：park 应该报告死锁而不是立即返回
+    base_a.unpark();
+    let err = panic::catch_unwind(park).unwrap_err();
+    assert!(
+        err.downcast_ref::<String>()
+            .unwrap()
+            .starts_with("deadlock")
+    );
+    drop(b);
+
+    a.init();
+    assert_eq!(a.run().tasks, 2);
+    assert_eq!(kept.join(), 2);
+}
+
+// 栈展开时记下来，检查到期的 timeout 执行了 Drop
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+// f 阻塞在某个等待上，10 毫秒的 timeout 到期后展开 f 的栈返回
+fn assert_elapses<T>(f: impl FnOnce() -> T) {
+    let dropped = Rc::new(Cell::new(false));
+    let flag = DropFlag(Rc::clone(&dropped));
+    let start = Instant::now();
+    let result = time::timeout(Duration::from_millis(10), move || {
+        let _flag = flag;
+        f();
+        unreachable!("the wait returned before the timeout");
+    });
+    result.unwrap_err();
+    assert!(dropped.get());
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn timeout_interrupts_every_kind_of_wait() {
+    let mut rt = runtime();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    spawn(move || {
+        assert_elapses(park);
+        assert_elapses(|| time::sleep(Duration::from_secs(3600)));
+        assert_elapses(|| socket.recv(&mut [0; 16]));
+
+        // join 被打断时句柄随栈展开丢弃，协程继续运行到结束
+        let slow = spawn(|| time::sleep(Duration::from_millis(200)));
+        assert_elapses(|| slow.join());
+        assert_elapses(|| spawn_blocking(|| std::thread::sleep(Duration::from_millis(200))));
+    });
+
+    let start = Instant::now();
+    let summary = rt.run();
+    assert_eq!(summary.tasks, 2);
+    assert_eq!(summary.panics, 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn outer_timeout_firing_first_unwinds_the_inner_one() {
+    let mut rt = runtime();
+    let inner_returned = Rc::new(Cell::new(false));
+    let flag = Rc::clone(&inner_returned);
+    let outer = spawn(move || {
+        time::timeout(Duration::from_millis(10), || {
+            let inner = time::timeout(Duration::from_secs(3600), || {
+                time::sleep(Duration::from_secs(3600));
+            });
+            // 外层到期时内层的 timeout 不能把它当成自己的到期处理
+            flag.set(true);
+            inner
+        })
+    });
+
+    let start = Instant::now();
+    assert!(outer.join().is_err());
+    assert!(!inner_returned.get());
+    assert_eq!(rt.run().panics, 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn timeout_that_completes_in_time_returns_the_value() {
+    let mut rt = runtime();
+    let handle = spawn(|| {
+        let value = time::timeout(Duration::from_secs(3600), || {
+            time::sleep(Duration::from_millis(1));
+            yield_now();
+            42
+        });
+        // 内层正常结束，外层仍然有效
+        let nested = time::timeout(Duration::from_secs(3600), || {
+            time::timeout(Duration::from_millis(1), || 1).unwrap()
+                + time::timeout(Duration::from_millis(10), park).map_or(1, |_| 0)
+        });
+        (value, nested)
+    });
+
+    assert_eq!(handle.join(), (Ok(42), Ok(2)));
+    // 结束了的 timeout 不再让 run 等到它的到期时间
+    let start = Instant::now();
+    assert_eq!(rt.run().tasks, 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn run_does_not_wait_for_finished_or_expired_timeouts() {
+    let mut rt = runtime();
+    for _ in 0..3 {
+        spawn(|| {
+            // 正常结束的和已经到期的
+            time::timeout(Duration::from_secs(3600), yield_now).unwrap();
+            time::timeout(Duration::from_millis(1), park).unwrap_err();
+        });
+    }
+    // 被取消的睡眠也不再留在时间轮中
+    let sleeper = spawn(|| time::sleep(Duration::from_secs(3600)));
+    spawn(move || {
+        yield_now();
+        assert!(sleeper.cancel());
+    });
+
+    let start = Instant::now();
+    let summary = rt.run();
+    assert_eq!(summary.tasks, 5);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+// join 已经被取消的协程会 panic
+fn assert_join_cancelled<T: 'static>(handle: rustcoro::JoinHandle<T>) {
+    let id = handle.id();
+    let err = panic::catch_unwind(AssertUnwindSafe(|| {
+        handle.join();
+    }))
+    .unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert_eq!(message, &format!("joined coroutine {} was cancelled", id));
+}
+
+#[test]
+fn unpark_before_park_leaves_a_permit() {
+    let mut rt = runtime();
+    let handle = spawn(|| {
+        // 自己给自己留下许可，park 立即返回，许可只能用一次
+        current().unpark();
+        park();
+        time::timeout(Duration::from_millis(10), park).is_err()
+    });
+
+    let parked = Rc::new(RefCell::new(None::<Coroutine>));
+    let waiter = {
+        let parked = Rc::clone(&parked);
+        spawn(move || {
+            *parked.borrow_mut() = Some(current());
+            yield_now(); // 对方在这之间 unpark
+            park();
+        })
+    };
+    spawn(move || parked.borrow().unwrap().unpark());
+
+    assert!(handle.join());
+    waiter.join();
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn stale_handle_does_not_wake_a_reused_slot() {
+    let mut rt = runtime();
+    let first = spawn(current);
+    let stale = first.join();
+    // 槽位已经空出来，下一个协程复用它
+    let second = spawn(|| time::timeout(Duration::from_millis(20), park).is_err());
+    assert_eq!(second.id(), stale.id());
+    spawn(move || stale.unpark());
+
+    assert!(second.join(), "stale handle woke the new coroutine");
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn cancel_ready_blocked_and_sleeping_coroutines() {
+    let mut rt = runtime();
+
+    // 还没开始运行的协程不再运行
+    let started = Rc::new(Cell::new(false));
+    let ready = {
+        let started = Rc::clone(&started);
+        spawn(move || started.set(true))
+    };
+    assert!(ready.cancel());
+
+    // 阻塞和睡眠中的协程展开栈，执行 Drop
+    let (blocked_dropped, sleeping_dropped) =
+        (Rc::new(Cell::new(false)), Rc::new(Cell::new(false)));
+    let blocked = {
+        let flag = DropFlag(Rc::clone(&blocked_dropped));
+        spawn(move || {
+            let _flag = flag;
+            park();
+        })
+    };
+    let sleeping = {
+        let flag = DropFlag(Rc::clone(&sleeping_dropped));
+        spawn(move || {
+            let _flag = flag;
+            time::sleep(Duration::from_secs(3600));
+        })
+    };
+    let finished = spawn(|| ());
+
+    let canceller = spawn(move || {
+        yield_now(); // 让 blocked 和 sleeping 先阻塞
+        assert!(blocked.cancel());
+        assert!(sleeping.cancel());
+        (blocked, sleeping)
+    });
+    let (blocked, sleeping) = canceller.join();
+
+    finished.join();
+    assert_join_cancelled(ready);
+    assert_join_cancelled(blocked);
+    assert_join_cancelled(sleeping);
+
+    let start = Instant::now();
+    let summary = rt.run();
+    assert!(!started.get());
+    assert!(blocked_dropped.get() && sleeping_dropped.get());
+    assert_eq!(summary.panics, 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn finished_coroutine_cannot_be_cancelled() {
+    let mut rt = runtime();
+    let handle = spawn(|| 1);
+    rt.run();
+    assert!(handle.is_finished());
+    assert!(!handle.cancel());
+    assert_eq!(handle.join(), 1);
+}
+
+#[test]
+fn spawn_fails_at_the_thread_limit_and_reuses_joined_slots() {
+    let mut rt = Runtime::new().with_max_threads(2);
+    rt.init();
+    let first = spawn(|| 1);
+    let second = spawn(|| 2);
+
+    let err = Runtime::spawnf(|| 3).err().unwrap();
+    assert!(matches!(err, SpawnError::LimitReached(2)), "{}", err);
+    assert!(Builder::new().spawn(|| 3).is_err());
+
+    // join 取走结果之后槽位空出来，新的协程复用它
+    let id = first.id();
+    assert_eq!(first.join(), 1);
+    let third = Runtime::spawnf(|| 3).unwrap();
+    assert_eq!(third.id(), id);
+    assert!(Runtime::spawnf(|| 4).is_err());
+
+    assert_eq!(second.join() + third.join(), 5);
+    assert_eq!(rt.run().panics, 0);
+}
+
+// 在栈上放一个 N 字节的数组并写满，不让编译器优化掉
+fn touch_stack<const N: usize>() -> u8 {
+    let mut buf = [0_u8; N];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    std::hint::black_box(&mut buf)[N - 1]
+}
+
+#[test]
+fn stack_usage_covers_the_deepest_frame() {
+    const ARRAY: usize = 64 * 1024;
+
+    let mut rt = Runtime::new().with_stack_usage(true);
+    rt.init();
+    let handle = spawn(touch_stack::<ARRAY>);
+    let id = handle.id();
+    rt.run();
+
+    // 结束后栈已经回到池子里，仍然可以查询结束时的峰值
+    let peak = rt.stack_usage(id).unwrap();
+    assert!(
+        peak >= ARRAY,
+        "peak {} below the {} byte array",
+        peak,
+        ARRAY
+    );
+    assert!(peak < ARRAY + 32 * 1024, "{}", peak);
+    assert_eq!(handle.stack_usage(), Some(peak));
+    handle.join();
+}
+
+#[test]
+fn stack_usage_is_none_without_painting() {
+    let mut rt = runtime();
+    let handle = spawn(touch_stack::<4096>);
+    let id = handle.id();
+    rt.run();
+    assert_eq!(rt.stack_usage(id), None);
+    assert_eq!(handle.stack_usage(), None);
+    handle.join();
+}
+
+#[test]
+fn missed_deadlines_are_counted_once_per_deadline() {
+    let mut rt = Runtime::new().with_scheduler(Edf::new());
+    rt.init();
+    let order = Rc::new(RefCell::new(Vec::new()));
+
+    let relaxed = {
+        let order = Rc::clone(&order);
+        spawn(move || {
+            order.borrow_mut().push("relaxed");
+            yield_now();
+        })
+    };
+    // 截止时间已经过了：最先运行，离开 CPU 时记一次错过
+    let late = {
+        let order = Rc::clone(&order);
+        Builder::new()
+            .deadline(Instant::now())
+            .spawn(move || {
+                order.borrow_mut().push("late");
+                for _ in 0..3 {
+                    yield_now();
+                }
+                // 新的截止时间重新开始检查
+                set_deadline(Instant::now());
+                yield_now();
+            })
+            .unwrap()
+    };
+    let on_time = Builder::new()
+        .deadline(Instant::now() + Duration::from_secs(3600))
+        .spawn(yield_now)
+        .unwrap();
+    let (late_id, on_time_id) = (late.id(), on_time.id());
+
+    assert_eq!(rt.run().panics, 0);
+    assert_eq!(order.borrow()[0], "late");
+    assert_eq!(late.missed_deadlines(), 2);
+    assert_eq!(rt.missed_deadlines(late_id), 2);
+    assert_eq!(rt.missed_deadlines(on_time_id), 0);
+    assert_eq!(relaxed.missed_deadlines(), 0);
+}
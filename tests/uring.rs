@@ -0,0 +1,126 @@
+#![cfg(feature = "io-uring")]
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rustcoro::{Runtime, io, spawn, time};
+
+// 每个测试在自己的 OS 线程上运行，各自创建 runtime
+fn runtime() -> Runtime {
+    let mut rt = Runtime::new();
+    rt.init();
+    rt
+}
+
+// 测试用的文件路径，每个进程、每个测试不同
+fn file_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("rustcoro-{}-{}", std::process::id(), name))
+}
+
+// 阻塞的 TCP socket，io_uring 的 fd 不需要是非阻塞的
+fn tcp_socket() -> OwnedFd {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    assert!(fd >= 0, "{}", std::io::Error::last_os_error());
+    unsafe { OwnedFd::from_raw_fd(fd) }
+}
+
+#[test]
+fn read_at_and_write_at_on_a_regular_file() {
+    let mut rt = runtime();
+    let path = file_path("read-at");
+    fs::write(&path, b"hello, io_uring").unwrap();
+
+    let file = File::options().read(true).write(true).open(&path).unwrap();
+    let handle = spawn(move || {
+        let fd = file.as_raw_fd();
+        let mut buf = [0; 8];
+        let n = io::read_at(fd, &mut buf, 7).unwrap();
+        let tail = buf[..n].to_vec();
+
+        assert_eq!(io::write_at(fd, b"HELLO", 0).unwrap(), 5);
+        // 读到文件末尾返回 0
+        assert_eq!(io::read_at(fd, &mut buf, 1 << 20).unwrap(), 0);
+        // 指定 offset 的读写不改变文件的当前位置
+        let n = io::read(fd, &mut buf).unwrap();
+        (tail, buf[..n].to_vec())
+    });
+
+    let (tail, head) = handle.join();
+    assert_eq!(tail, b"io_uring");
+    assert_eq!(head, b"HELLO, i");
+    assert_eq!(rt.run().panics, 0);
+    assert_eq!(fs::read(&path).unwrap(), b"HELLO, io_uring");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn accept_and_connect() {
+    let mut rt = runtime();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // 服务端先开始等，accept 要让出执行权给客户端
+    spawn(move || {
+        let conn = io::accept(listener.as_raw_fd()).unwrap();
+        let mut stream = TcpStream::from(conn);
+        stream.write_all(b"welcome").unwrap();
+    });
+
+    let client = spawn(move || {
+        let socket = tcp_socket();
+        io::connect(socket.as_raw_fd(), &addr).unwrap();
+        let mut stream = TcpStream::from(socket);
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).unwrap();
+        greeting
+    });
+
+    assert_eq!(client.join(), "welcome");
+    assert_eq!(rt.run().panics, 0);
+}
+
+#[test]
+fn connect_refused() {
+    let _rt = runtime();
+    // 先占一个端口再关掉，连接会被拒绝
+    let addr: SocketAddr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let err = spawn(move || io::connect(tcp_socket().as_raw_fd(), &addr).unwrap_err()).join();
+    assert_eq!(err.kind(), std::io::ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn timeout_cancels_a_pending_accept() {
+    let mut rt = runtime();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = spawn(move || {
+        let fd = listener.as_raw_fd();
+        let start = Instant::now();
+        let result = time::timeout(Duration::from_millis(10), || io::accept(fd));
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // 被撤销的 accept 不会拿走后来的连接
+        let client = std::thread::spawn(move || TcpStream::connect(addr).unwrap());
+        let conn = io::accept(fd).unwrap();
+        let client = client.join().unwrap();
+        assert_eq!(
+            TcpStream::from(conn).peer_addr().unwrap(),
+            client.local_addr().unwrap()
+        );
+    });
+
+    server.join();
+    let start = Instant::now();
+    assert_eq!(rt.run().panics, 0);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}